@@ -0,0 +1,169 @@
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::components::*;
+use crate::synth::*;
+use crate::{Boom, Hit, ShotFired};
+
+const ENGINE_VOLUME: f32 = 0.3;
+// a ship scraping along a moon is hit every physics step, it only clanks this often
+const SHIELD_HIT_INTERVAL: f32 = 0.15;
+
+#[derive(Resource, Clone, Copy, PartialEq, Eq)]
+pub enum AudioBackend {
+    Device,
+    // for headless runs: sounds are only logged, the audio plugin is not even loaded
+    Null,
+}
+
+pub fn audio_backend_from_args() -> AudioBackend {
+    if std::env::args().any(|arg| arg == "--no-audio") {
+        AudioBackend::Null
+    } else {
+        AudioBackend::Device
+    }
+}
+
+pub fn audio_device(backend: Res<AudioBackend>) -> bool {
+    *backend == AudioBackend::Device
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Sound {
+    Shot,
+    Boom,
+    ShieldHit,
+}
+
+impl Sound {
+    pub fn samples(self) -> Vec<f32> {
+        match self {
+            Sound::Shot => gain(&sweep(1400., 300., 0.12), 0.4),
+            Sound::Boom => mix(&noise_burst(0.8, 6., 7), &sweep(120., 30., 0.5)),
+            Sound::ShieldHit => mix(
+                &sweep(500., 900., 0.1),
+                &gain(&noise_burst(0.1, 8., 3), 0.3),
+            ),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Sounds {
+    shot: Handle<AudioSource>,
+    boom: Handle<AudioSource>,
+    shield_hit: Handle<AudioSource>,
+    engine: Handle<AudioSource>,
+}
+
+impl Sounds {
+    fn get(&self, sound: Sound) -> Handle<AudioSource> {
+        match sound {
+            Sound::Shot => self.shot.clone(),
+            Sound::Boom => self.boom.clone(),
+            Sound::ShieldHit => self.shield_hit.clone(),
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct PlaySound {
+    pub sound: Sound,
+    pub volume: f32,
+}
+
+fn source(samples: &[f32]) -> AudioSource {
+    AudioSource {
+        bytes: to_wav(samples).into(),
+    }
+}
+
+pub fn setup_sounds(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    commands.insert_resource(Sounds {
+        shot: sources.add(source(&Sound::Shot.samples())),
+        boom: sources.add(source(&Sound::Boom.samples())),
+        shield_hit: sources.add(source(&Sound::ShieldHit.samples())),
+        engine: sources.add(source(&engine_loop(11))),
+    });
+}
+
+// turns gameplay events into sounds, independent of the backend
+pub fn queue_sounds(
+    mut shots: EventReader<ShotFired>,
+    mut booms: EventReader<Boom>,
+    mut hits: EventReader<Hit>,
+    ships: Query<(), With<Ship>>,
+    mut writer: EventWriter<PlaySound>,
+    mut last_hit: Local<HashMap<Entity, f32>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for _ in shots.read() {
+        writer.send(PlaySound {
+            sound: Sound::Shot,
+            volume: 1.,
+        });
+    }
+    for _ in booms.read() {
+        writer.send(PlaySound {
+            sound: Sound::Boom,
+            volume: 1.,
+        });
+    }
+    last_hit.retain(|_, at| now - *at < SHIELD_HIT_INTERVAL);
+    for hit in hits.read() {
+        if !ships.contains(hit.entity) || last_hit.contains_key(&hit.entity) {
+            continue;
+        }
+        last_hit.insert(hit.entity, now);
+        writer.send(PlaySound {
+            sound: Sound::ShieldHit,
+            volume: hit.damage.clamp(0.2, 1.),
+        });
+    }
+}
+
+pub fn play_sounds(
+    mut reader: EventReader<PlaySound>,
+    sounds: Res<Sounds>,
+    mut commands: Commands,
+) {
+    for event in reader.read() {
+        commands.spawn(AudioBundle {
+            source: sounds.get(event.sound),
+            settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(event.volume)),
+        });
+    }
+}
+
+pub fn log_sounds(mut reader: EventReader<PlaySound>) {
+    for event in reader.read() {
+        trace!("null audio: {:?} at volume {}", event.sound, event.volume);
+    }
+}
+
+pub fn attach_engine_sound(
+    ships: Query<Entity, (Added<Thruster>, Without<Ghost>)>,
+    sounds: Res<Sounds>,
+    mut commands: Commands,
+) {
+    for entity in &ships {
+        commands.entity(entity).insert(AudioBundle {
+            source: sounds.engine.clone(),
+            settings: PlaybackSettings::LOOP
+                .paused()
+                .with_volume(Volume::new(ENGINE_VOLUME)),
+        });
+    }
+}
+
+pub fn engine_sound(ships: Query<(&Thruster, &AudioSink)>) {
+    for (thruster, sink) in &ships {
+        if thruster.thrusting && sink.is_paused() {
+            sink.play();
+        } else if !thruster.thrusting && !sink.is_paused() {
+            sink.pause();
+        }
+    }
+}
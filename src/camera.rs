@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::components::*;
+use crate::level::*;
+
+const CAMERA_PADDING: f32 = 120.;
+const MIN_VIEW_SIZE: f32 = 300.;
+const CAMERA_SMOOTHING: f32 = 3.;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    FrameShips,
+    WholeArena,
+}
+
+#[derive(Component)]
+pub struct CameraController {
+    pub mode: CameraMode,
+    pub padding: f32,
+    // smoothed state, written to the transform and projection every frame
+    pub center: Vec2,
+    pub zoom: f32,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController {
+            mode: CameraMode::FrameShips,
+            padding: CAMERA_PADDING,
+            center: Vec2::ZERO,
+            zoom: 1.,
+        }
+    }
+}
+
+pub fn toggle_camera_mode(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut cameras: Query<&mut CameraController>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        for mut controller in &mut cameras {
+            controller.mode = match controller.mode {
+                CameraMode::FrameShips => CameraMode::WholeArena,
+                CameraMode::WholeArena => CameraMode::FrameShips,
+            };
+        }
+    }
+}
+
+// the race ghost is not followed, and the camera's own transform is borrowed separately
+type FramedShips = (With<Ship>, Without<Ghost>, Without<CameraController>);
+
+// Ships are framed where they really are. Showing a pair across the wrap seam would need
+// ghost copies of the whole arena, so such a pair simply makes the camera zoom out.
+pub fn frame_camera(
+    mut cameras: Query<(
+        &mut CameraController,
+        &mut Transform,
+        &mut OrthographicProjection,
+    )>,
+    ships: Query<&Transform, FramedShips>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+    if window_size.min_element() <= 0. {
+        return;
+    }
+    let arena = Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(level.space_size));
+
+    for (mut controller, mut transform, mut projection) in &mut cameras {
+        let framed = match controller.mode {
+            CameraMode::WholeArena => None,
+            CameraMode::FrameShips => ships
+                .iter()
+                .map(|ship| Rect::from_center_size(ship.translation.xy(), Vec2::ZERO))
+                .reduce(|a, b| a.union(b))
+                .map(|rect| rect.inset(controller.padding)),
+        };
+        let view = match framed {
+            Some(rect) => {
+                let size = rect.size().max(Vec2::splat(MIN_VIEW_SIZE));
+                Rect::from_center_size(rect.center(), size)
+            }
+            None => arena,
+        };
+
+        let target_zoom = (view.size() / window_size)
+            .max_element()
+            .min((arena.size() / window_size).max_element());
+        let visible = window_size * target_zoom;
+        // keep the view inside the arena, there is nothing to see beyond the wrap
+        let slack = ((arena.size() - visible) / 2.).max(Vec2::ZERO);
+        let target_center = view.center().clamp(-slack, slack);
+
+        let blend = 1. - (-CAMERA_SMOOTHING * time.delta_seconds()).exp();
+        controller.zoom += (target_zoom - controller.zoom) * blend;
+        controller.center = controller.center.lerp(target_center, blend);
+
+        projection.scale = controller.zoom;
+        transform.translation = controller.center.extend(transform.translation.z);
+    }
+}
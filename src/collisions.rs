@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::level::*;
+
+// every collider belongs to one category, set by the function that spawns it
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub enum CollisionCategory {
+    Ship,
+    Projectile,
+    Asteroid,
+    Moon,
+    // stars, black holes swallow things without touching them and are effects
+    Hazard,
+    // ore and flags, sensors that only ships pick up
+    Pickup,
+    // things that are only there to be seen, like the race ghost
+    Effect,
+}
+
+impl CollisionCategory {
+    pub const ALL: [CollisionCategory; 7] = [
+        CollisionCategory::Ship,
+        CollisionCategory::Projectile,
+        CollisionCategory::Asteroid,
+        CollisionCategory::Moon,
+        CollisionCategory::Hazard,
+        CollisionCategory::Pickup,
+        CollisionCategory::Effect,
+    ];
+
+    fn group(self) -> Group {
+        Group::from_bits_truncate(1 << self as u32)
+    }
+}
+
+// which categories touch each other, a pair works both ways
+#[derive(Resource)]
+pub struct CollisionMatrix {
+    pairs: Vec<(CollisionCategory, CollisionCategory)>,
+}
+
+impl CollisionMatrix {
+    pub fn for_mode(mode: GameMode) -> Self {
+        use CollisionCategory::*;
+        let mut pairs = vec![
+            (Ship, Projectile),
+            (Ship, Asteroid),
+            (Ship, Moon),
+            (Ship, Hazard),
+            (Ship, Pickup),
+            (Projectile, Asteroid),
+            (Projectile, Moon),
+            (Projectile, Hazard),
+            (Asteroid, Moon),
+            (Asteroid, Hazard),
+            (Moon, Moon),
+            (Moon, Hazard),
+        ];
+        // racers fly through each other instead of knocking each other off the line
+        if mode != GameMode::Race {
+            pairs.push((Ship, Ship));
+        }
+        CollisionMatrix { pairs }
+    }
+
+    pub fn collides(&self, a: CollisionCategory, b: CollisionCategory) -> bool {
+        self.pairs
+            .iter()
+            .any(|pair| *pair == (a, b) || *pair == (b, a))
+    }
+
+    pub fn groups(&self, category: CollisionCategory) -> CollisionGroups {
+        let filters = CollisionCategory::ALL
+            .into_iter()
+            .filter(|other| self.collides(category, *other))
+            .fold(Group::NONE, |filters, other| filters | other.group());
+        CollisionGroups::new(category.group(), filters)
+    }
+}
+
+// runs after the spawns of the frame and before rapier creates their colliders
+pub fn apply_collision_groups(
+    categories: Query<(Entity, &CollisionCategory), Changed<CollisionCategory>>,
+    matrix: Res<CollisionMatrix>,
+    mut commands: Commands,
+) {
+    for (entity, category) in &categories {
+        let groups = matrix.groups(*category);
+        commands.entity(entity).insert((
+            groups,
+            SolverGroups::new(groups.memberships, groups.filters),
+        ));
+    }
+}
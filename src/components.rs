@@ -1,38 +1,407 @@
-
 use bevy::prelude::*;
 
+use crate::level::{FlightModel, ShieldLayout};
+
 #[derive(Component)]
 pub struct Debris;
 
+#[derive(Component)]
+pub struct Shot;
+
 #[derive(Component)]
 pub struct EnergyDisplay {
     pub ship: Entity,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum HudBarKind {
+    Shield,
+    Hull,
+    Cooldown,
+    Fuel,
+}
+
+#[derive(Component)]
+pub struct HudBar {
+    pub player: u8,
+    pub kind: HudBarKind,
+}
+
+#[derive(Component)]
+pub struct TeamScoreText;
+
+#[derive(Component)]
+pub struct ScoreText {
+    pub player: u8,
+}
+
 #[derive(Component)]
-pub struct Asteroid;
+pub struct ClassChoiceText {
+    pub player: u8,
+}
+
+#[derive(Component)]
+pub struct ClassSelectionRoot;
+
+#[derive(Component)]
+pub struct OffscreenMarker {
+    pub player: u8,
+}
+
+// what an asteroid is made of, each kind pays for a different upgrade
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Ore {
+    Iron,
+    Ice,
+    Crystal,
+}
+
+impl Ore {
+    pub const ALL: [Ore; 3] = [Ore::Iron, Ore::Ice, Ore::Crystal];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Component)]
+pub struct Asteroid {
+    pub ore: Ore,
+}
+
+#[derive(Component)]
+pub struct OrePickup {
+    pub ore: Ore,
+}
+
+// ore picked up but not yet delivered to a moon, lost with the ship
+#[derive(Component, Default)]
+pub struct Cargo {
+    pub ore: [u32; 3],
+}
+
+impl Cargo {
+    pub fn total(&self) -> u32 {
+        self.ore.iter().sum()
+    }
+}
+
+#[derive(Component)]
+pub struct CargoText {
+    pub player: u8,
+}
 
 #[derive(Component)]
 pub struct Ship {
     pub player: u8,
 }
 
+#[derive(Component)]
+pub struct Owner {
+    pub player: u8,
+}
+
+// carried by ships and their shots
+#[derive(Component, Clone, Copy, PartialEq)]
+pub struct Team {
+    pub id: u8,
+}
+
 #[derive(Component)]
 pub struct Moon {}
 
+#[derive(Component)]
+pub enum Hazard {
+    BlackHole {
+        horizon: f32,
+    },
+    Star {
+        size: f32,
+        heat_radius: f32,
+        heat: f32,
+    },
+}
+
+// the outline of a moon as one radius per tile, 1 is untouched ground
+#[derive(Component)]
+pub struct MoonSurface {
+    pub radii: Vec<f32>,
+    // the mass before the first crater, later the mass shrinks with the area
+    pub initial_mass: Option<f32>,
+}
+
+impl MoonSurface {
+    pub fn new(tiles: usize) -> Self {
+        MoonSurface {
+            radii: vec![1.; tiles],
+            initial_mass: None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct CircularOrbit;
+
+#[derive(Component, Clone, Copy)]
+pub struct Rail {
+    pub around: Option<Entity>,
+    pub center: Vec2,
+    pub radius: f32,
+    pub phase: f32,
+    pub angular_speed: f32,
+}
+
+impl Rail {
+    pub fn offset_at(&self, time: f32) -> Vec2 {
+        Vec2::from_angle(self.phase + self.angular_speed * time) * self.radius
+    }
+}
+
+// soaks up damage before it reaches the hull and recharges over time
 #[derive(Component)]
 pub struct Shield {
+    // one arc covers the whole ship, two arcs cover the front and the rear half
+    pub arcs: Vec<f32>,
+    // per arc
+    pub capacity: f32,
+    // multiplies the level's heal speed
+    pub regen: f32,
+}
+
+impl Shield {
+    pub fn new(capacity: f32, regen: f32, layout: ShieldLayout) -> Self {
+        Shield {
+            arcs: vec![capacity; layout.arcs()],
+            capacity,
+            regen,
+        }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.arcs.iter().sum::<f32>() / (self.capacity * self.arcs.len() as f32)
+    }
+
+    pub fn arc_fraction(&self, arc: usize) -> f32 {
+        self.arcs
+            .get(arc)
+            .map_or(0., |energy| energy / self.capacity)
+    }
+
+    // any arc at zero, a carrier in this state loses its flag
+    pub fn is_broken(&self) -> bool {
+        self.arcs.iter().any(|energy| *energy <= 0.)
+    }
+
+    // the arc facing a hit from `direction`, given in the ship's own frame where forward is +y
+    pub fn arc_towards(&self, direction: Vec2) -> usize {
+        if self.arcs.len() > 1 && direction.y < 0. {
+            1
+        } else {
+            0
+        }
+    }
+
+    // takes what the arc can hold and returns the rest of the damage
+    pub fn absorb(&mut self, arc: usize, damage: f32) -> f32 {
+        let energy = &mut self.arcs[arc];
+        let absorbed = damage.min(*energy);
+        *energy -= absorbed;
+        damage - absorbed
+    }
+}
+
+// what is left of a ship, asteroid or moon, it goes up in a boom below zero
+#[derive(Component)]
+pub struct Hull {
+    pub integrity: f32,
+    pub capacity: f32,
+}
+
+impl Hull {
+    pub fn new(capacity: f32) -> Self {
+        Hull {
+            integrity: capacity,
+            capacity,
+        }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.integrity / self.capacity
+    }
+}
+
+// the ring drawn around a ship, one per shield arc, remembers the last energy to notice hits
+#[derive(Component)]
+pub struct ShieldRing {
+    pub arc: usize,
     pub energy: f32,
+    pub flash: f32,
+    pub color: Color,
 }
 
 #[derive(Component)]
 pub struct Thruster {
     pub thruster_time: f32,
+    pub thrusting: bool,
+    pub fuel: f32,
+    // impulse per second while thrusting
+    pub thrust: f32,
+}
+
+// how a ship turns and how much the flight computer helps, tuned per ship class
+#[derive(Component, Clone, Copy)]
+pub struct Flight {
+    pub model: FlightModel,
+    pub turn_rate: f32,
+    pub angular_acceleration: f32,
+    pub rotation_damping: f32,
+    pub linear_drag: f32,
+    pub max_speed: Option<f32>,
+}
+
+// what the pilot wants the ship to do this frame, filled in from the keyboard or by the AI
+#[derive(Component, Default)]
+pub struct Controls {
+    pub thrust: bool,
+    // 1 turns left, -1 turns right
+    pub turn: f32,
+    pub shoot: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PowerSystem {
+    Shields,
+    Weapons,
+    Engines,
+}
+
+impl PowerSystem {
+    pub const ALL: [PowerSystem; 3] = [
+        PowerSystem::Shields,
+        PowerSystem::Weapons,
+        PowerSystem::Engines,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// a fixed budget of power pips shared by the shields, the guns and the engines
+#[derive(Component)]
+pub struct Reactor {
+    // indexed by `PowerSystem::index`, always adds up to `Reactor::BUDGET`
+    pub pips: [u32; 3],
+}
+
+impl Default for Reactor {
+    fn default() -> Self {
+        // an even split
+        Reactor {
+            pips: [Reactor::BUDGET / 3; 3],
+        }
+    }
+}
+
+impl Reactor {
+    pub const BUDGET: u32 = 6;
+    pub const MAX_PIPS: u32 = 4;
+
+    // moves one pip to `system`, taken from whichever other system has the most
+    pub fn route(&mut self, system: PowerSystem) {
+        if self.pips[system.index()] >= Reactor::MAX_PIPS {
+            return;
+        }
+        let donor = PowerSystem::ALL
+            .into_iter()
+            .filter(|other| *other != system)
+            .max_by_key(|other| self.pips[other.index()])
+            .unwrap();
+        if self.pips[donor.index()] > 0 {
+            self.pips[donor.index()] -= 1;
+            self.pips[system.index()] += 1;
+        }
+    }
+
+    // 1 at an even split, from half with no pips up to one and a half at the maximum
+    pub fn factor(&self, system: PowerSystem) -> f32 {
+        0.5 + 0.25 * self.pips[system.index()] as f32
+    }
+}
+
+#[derive(Component)]
+pub struct PowerText {
+    pub player: u8,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NpcKind {
+    // closes in and shoots from short range
+    Chaser,
+    // keeps its distance and shoots from afar
+    Sniper,
+    // unarmed, flies straight into its target
+    Rammer,
+}
+
+#[derive(Component)]
+pub struct Npc {
+    pub kind: NpcKind,
+}
+
+#[derive(Component)]
+pub struct WaveText;
+
+#[derive(Component)]
+pub struct HillProgressBar;
+
+#[derive(Component)]
+pub struct Flag {
+    pub team: u8,
+    pub home: Vec2,
+    // the enemy ship towing the flag
+    pub carrier: Option<Entity>,
+    // set while the flag floats free after its carrier was shot down
+    pub dropped_at: Option<f32>,
+}
+
+// replays the best lap, flies through everything without touching it
+#[derive(Component)]
+pub struct Ghost;
+
+#[derive(Component)]
+pub struct RaceText {
+    pub player: u8,
+}
+
+impl Flag {
+    pub fn at_home(&self) -> bool {
+        self.carrier.is_none() && self.dropped_at.is_none()
+    }
+}
+
+#[derive(Component)]
+pub struct Trajectory {
+    pub visible: bool,
+    pub seconds: f32,
 }
 
 #[derive(Component)]
 pub struct Gun {
     pub time: f32,
+    pub cooldown: f32,
+    pub shot_speed: f32,
+    pub shot_lifetime: f32,
+    // shots per trigger pull, fanned out side by side
+    pub barrels: u32,
+    // how far ahead of the ship's centre the shots appear, clear of its own collider
+    pub muzzle: f32,
+}
+
+impl Gun {
+    pub fn range(&self) -> f32 {
+        self.shot_speed * self.shot_lifetime
+    }
 }
 
 #[derive(Component)]
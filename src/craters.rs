@@ -0,0 +1,138 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy_rapier2d::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+use crate::line_mesh;
+use crate::orbit::PIXELS_PER_METER;
+use crate::particles::*;
+
+// an impact has to stop or bounce the impactor at least this hard to leave a mark
+const CRATER_SPEED: f32 = 150.;
+// craters never get wider than this part of the moon's radius
+const MAX_CRATER: f32 = 0.3;
+// the ground is never dug deeper than this, so a moon cannot be shot to pieces
+const MIN_RADIUS: f32 = 0.5;
+const EJECTA_COUNT: usize = 8;
+const EJECTA_SPEED: f32 = 60.;
+const EJECTA_LIFETIME: f32 = 1.2;
+
+fn outline(surface: &MoonSurface) -> Vec<Vec2> {
+    let tiles = surface.radii.len();
+    surface
+        .radii
+        .iter()
+        .enumerate()
+        .map(|(i, radius)| Vec2::from_angle(i as f32 * TAU / tiles as f32) * *radius)
+        .collect()
+}
+
+fn area(outline: &[Vec2]) -> f32 {
+    let n = outline.len();
+    (0..n)
+        .map(|i| outline[i].perp_dot(outline[(i + 1) % n]))
+        .sum::<f32>()
+        .abs()
+        / 2.
+}
+
+// a bowl around `angle`, deepest in the middle, both given in the moon's unit space
+fn carve(surface: &mut MoonSurface, angle: f32, width: f32, depth: f32) {
+    let tiles = surface.radii.len();
+    for (i, radius) in surface.radii.iter_mut().enumerate() {
+        let tile_angle = i as f32 * TAU / tiles as f32;
+        let distance = (tile_angle - angle + PI).rem_euclid(TAU) - PI;
+        if distance.abs() < width {
+            let bowl = 1. - (distance / width).powi(2);
+            *radius = (*radius - depth * bowl).max(MIN_RADIUS);
+        }
+    }
+}
+
+// runs before `kill`, so a moon destroyed in the same frame still takes its new shape
+#[allow(clippy::too_many_arguments)]
+pub fn carve_craters(
+    mut events: EventReader<ContactForceEvent>,
+    mut moons: Query<(&Transform, &mut MoonSurface, &ReadMassProperties), With<Moon>>,
+    impactors: Query<(&Transform, &Collider, &ReadMassProperties), Without<Moon>>,
+    mut particles: ResMut<Particles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    let mut carved = Vec::new();
+    for event in events.read() {
+        for (moon_entity, other) in [
+            (event.collider1, event.collider2),
+            (event.collider2, event.collider1),
+        ] {
+            let (Ok((moon_transform, mut surface, moon_mass)), Ok((transform, collider, mass))) =
+                (moons.get_mut(moon_entity), impactors.get(other))
+            else {
+                continue;
+            };
+            if mass.mass <= 0. {
+                continue;
+            }
+            // how hard the impactor was stopped, in pixels per second
+            let speed_change =
+                event.total_force_magnitude * time.delta_seconds() / mass.mass * PIXELS_PER_METER;
+            if speed_change < CRATER_SPEED {
+                continue;
+            }
+
+            let size = moon_transform.scale.x;
+            let impactor_size = collider.raw.compute_local_aabb().half_extents().max();
+            let crater =
+                (impactor_size * (speed_change / CRATER_SPEED).sqrt()).min(size * MAX_CRATER);
+            let offset = level.wrap(transform.translation.xy() - moon_transform.translation.xy());
+            let local = (moon_transform.rotation.inverse() * offset.extend(0.)).xy();
+            surface.initial_mass.get_or_insert(moon_mass.mass);
+            carve(
+                &mut surface,
+                local.to_angle(),
+                crater / size,
+                crater / size / 2.,
+            );
+            if !carved.contains(&moon_entity) {
+                carved.push(moon_entity);
+            }
+
+            let ground = moon_transform.translation.xy() + offset.normalize_or_zero() * size;
+            for _ in 0..EJECTA_COUNT {
+                let spread = (rand::random::<f32>() - 0.5) * PI * 0.8;
+                let direction = Vec2::from_angle(offset.to_angle() + spread);
+                particles.emit(
+                    ground,
+                    direction * EJECTA_SPEED * (0.5 + rand::random::<f32>()),
+                    EJECTA_LIFETIME * (0.5 + rand::random::<f32>()),
+                    Color::GRAY,
+                );
+            }
+        }
+    }
+
+    // the outline, collider and mass follow the new ground once per frame
+    for entity in carved {
+        let Ok((_, surface, _)) = moons.get(entity) else {
+            continue;
+        };
+        let vertices = outline(surface);
+        let tiles = vertices.len() as u32;
+        let indices: Vec<[u32; 2]> = (0..tiles).map(|i| [i, (i + 1) % tiles]).collect();
+        let untouched = area(&outline(&MoonSurface::new(vertices.len())));
+        let mass = surface.initial_mass.unwrap_or(0.) * area(&vertices) / untouched;
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(line_mesh(
+                vertices.iter().map(|v| v.extend(0.)).collect(),
+                true,
+            ))),
+            Collider::convex_decomposition(&vertices, &indices),
+            ColliderMassProperties::Mass(mass),
+        ));
+    }
+}
@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy_rapier2d::prelude::*;
+
+use crate::collisions::CollisionCategory;
+use crate::components::*;
+use crate::level::*;
+use crate::players::*;
+use crate::teams::*;
+use crate::{create_flag, create_mesh, Boom, Celestial};
+
+const BASE_RADIUS: f32 = 50.;
+const FLAG_SIZE: f32 = 8.;
+// the rope between carrier and flag, the flag swings freely inside it
+const TOW_LENGTH: f32 = 40.;
+const FLAG_RETURN_TIME: f32 = 10.;
+const CAPTURE_POINTS: u32 = 10;
+
+#[derive(Resource)]
+pub struct Ctf {
+    pub bases: Vec<BaseSpec>,
+}
+
+impl Ctf {
+    fn base(&self, team: u8) -> Option<Vec2> {
+        self.bases
+            .iter()
+            .find(|base| base.team == team)
+            .map(|base| base.position)
+    }
+}
+
+pub fn setup_ctf(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    level: Res<Level>,
+) {
+    if level.rules.mode != GameMode::CaptureTheFlag {
+        return;
+    }
+    let mut bases = level.bases.clone();
+    let mut teams: Vec<u8> = level.spawns.iter().map(SpawnPoint::team).collect();
+    teams.sort_unstable();
+    teams.dedup();
+    for team in teams {
+        if bases.iter().any(|base| base.team == team) {
+            continue;
+        }
+        let starts: Vec<Vec2> = level
+            .spawns
+            .iter()
+            .filter(|spawn| spawn.team() == team)
+            .map(|spawn| spawn.position)
+            .collect();
+        bases.push(BaseSpec {
+            team,
+            position: starts.iter().sum::<Vec2>() / starts.len() as f32,
+        });
+    }
+
+    let mesh = meshes.add(create_mesh(create_flag, FLAG_SIZE));
+    for base in &bases {
+        spawn_flag(&mut commands, &mesh, &mut materials, base);
+    }
+    commands.insert_resource(Ctf { bases });
+}
+
+fn spawn_flag(
+    commands: &mut Commands,
+    mesh: &Handle<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    base: &BaseSpec,
+) {
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: mesh.clone().into(),
+            transform: Transform::from_translation(base.position.extend(0.)),
+            material: materials.add(ColorMaterial::from(team_color(base.team))),
+            ..Default::default()
+        },
+        // a light sensor, so it trails its carrier without bumping into anything,
+        // pinned in place while it waits at home
+        Celestial {
+            events: ActiveEvents::COLLISION_EVENTS,
+            body: RigidBody::Fixed,
+            ..Celestial::default()
+        },
+        Collider::ball(FLAG_SIZE),
+        Sensor,
+        ColliderMassProperties::Density(0.2),
+        CollisionCategory::Pickup,
+        Flag {
+            team: base.team,
+            home: base.position,
+            carrier: None,
+            dropped_at: None,
+        },
+    ));
+}
+
+pub fn return_flag(commands: &mut Commands, entity: Entity, flag: &mut Flag) {
+    flag.carrier = None;
+    flag.dropped_at = None;
+    commands.entity(entity).remove::<ImpulseJoint>().insert((
+        Transform::from_translation(flag.home.extend(0.)),
+        Velocity::zero(),
+        RigidBody::Fixed,
+    ));
+}
+
+pub fn touch_flags(
+    mut collisions: EventReader<CollisionEvent>,
+    mut flags: Query<(Entity, &mut Flag)>,
+    ships: Query<(&Team, &Shield), With<Ship>>,
+    mut commands: Commands,
+) {
+    for event in collisions.read() {
+        let CollisionEvent::Started(a, b, _) = event else {
+            continue;
+        };
+        for (flag_entity, ship_entity) in [(*a, *b), (*b, *a)] {
+            let (Ok((_, mut flag)), Ok((team, shield))) =
+                (flags.get_mut(flag_entity), ships.get(ship_entity))
+            else {
+                continue;
+            };
+            if team.id == flag.team {
+                // touching your own dropped flag sends it home
+                if flag.dropped_at.is_some() {
+                    return_flag(&mut commands, flag_entity, &mut flag);
+                }
+            } else if flag.carrier.is_none() && !shield.is_broken() {
+                flag.carrier = Some(ship_entity);
+                flag.dropped_at = None;
+                commands.entity(flag_entity).insert((
+                    RigidBody::Dynamic,
+                    ImpulseJoint::new(ship_entity, RopeJointBuilder::new(TOW_LENGTH)),
+                ));
+            }
+        }
+    }
+}
+
+// runs between the damage of the frame and `kill` despawning the carrier,
+// a carrier also loses the flag once its shield breaks
+pub fn drop_flags(
+    mut reader: EventReader<Boom>,
+    mut flags: Query<(Entity, &mut Flag)>,
+    shields: Query<&Shield>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let booms: Vec<Entity> = reader.read().map(|event| event.entity).collect();
+    for (entity, mut flag) in &mut flags {
+        let Some(carrier) = flag.carrier else {
+            continue;
+        };
+        let broken = shields.get(carrier).is_ok_and(Shield::is_broken);
+        if broken || booms.contains(&carrier) {
+            flag.carrier = None;
+            flag.dropped_at = Some(time.elapsed_seconds());
+            commands.entity(entity).remove::<ImpulseJoint>();
+        }
+    }
+}
+
+// the carrier and its flag wrap around the arena one after the other, this keeps the rope
+// from pulling the flag back across the whole arena in between
+pub fn keep_flags_with_carriers(
+    mut flags: Query<(&Flag, &mut Transform)>,
+    carriers: Query<&Transform, (With<Ship>, Without<Flag>)>,
+    level: Res<Level>,
+) {
+    for (flag, mut transform) in &mut flags {
+        let Some(carrier) = flag.carrier.and_then(|carrier| carriers.get(carrier).ok()) else {
+            continue;
+        };
+        let offset = transform.translation.xy() - carrier.translation.xy();
+        let wrapped = level.wrap(offset);
+        if wrapped != offset {
+            transform.translation = (carrier.translation.xy() + wrapped).extend(0.);
+        }
+    }
+}
+
+pub fn capture_flags(
+    ctf: Res<Ctf>,
+    mut flags: Query<(Entity, &mut Flag)>,
+    carriers: Query<(&Ship, &Team, &Transform)>,
+    mut players: ResMut<Players>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let home_teams: Vec<u8> = flags
+        .iter()
+        .filter(|(_, flag)| flag.at_home())
+        .map(|(_, flag)| flag.team)
+        .collect();
+    for (entity, mut flag) in &mut flags {
+        if flag
+            .dropped_at
+            .is_some_and(|at| time.elapsed_seconds() > at + FLAG_RETURN_TIME)
+        {
+            return_flag(&mut commands, entity, &mut flag);
+            continue;
+        }
+        let Some(carrier) = flag.carrier else {
+            continue;
+        };
+        let Ok((ship, team, ship_transform)) = carriers.get(carrier) else {
+            // the carrier is gone without exploding, leave the flag where it is
+            flag.carrier = None;
+            flag.dropped_at = Some(time.elapsed_seconds());
+            commands.entity(entity).remove::<ImpulseJoint>();
+            continue;
+        };
+        // a team can only score while its own flag is safe at home
+        let Some(base) = ctf.base(team.id) else {
+            continue;
+        };
+        if home_teams.contains(&team.id)
+            && ship_transform.translation.xy().distance(base) < BASE_RADIUS
+        {
+            if let Some(state) = players.get_mut(ship.player) {
+                state.score += CAPTURE_POINTS;
+            }
+            info!(
+                "player {} captured the flag of team {}",
+                ship.player + 1,
+                flag.team + 1
+            );
+            return_flag(&mut commands, entity, &mut flag);
+        }
+    }
+}
+
+pub fn draw_bases(ctf: Res<Ctf>, mut gizmos: Gizmos) {
+    for base in &ctf.bases {
+        gizmos.circle_2d(base.position, BASE_RADIUS, team_color(base.team));
+    }
+}
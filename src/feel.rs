@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use bevy::time::Real;
+use bevy_rapier2d::prelude::*;
+
+use crate::camera::*;
+use crate::components::*;
+use crate::{Boom, Hit};
+
+const MAX_SHAKE_OFFSET: f32 = 12.;
+const MAX_SHAKE_ANGLE: f32 = 0.03;
+const SHAKE_FREQUENCY: f32 = 25.;
+const TRAUMA_DECAY: f32 = 1.5;
+const FLASH_TIME: f32 = 0.12;
+const HIT_STOP_TIME: f32 = 0.08;
+const HIT_STOP_THRESHOLD: f32 = 0.5;
+const SHIP_BOOM_STRENGTH: f32 = 1.;
+const BOOM_STRENGTH: f32 = 0.4;
+
+// `--reduced-motion` turns off shaking and freezing for players sensitive to it
+#[derive(Resource)]
+pub struct FeelSettings {
+    pub shake: f32,
+    pub flash: bool,
+    pub hit_stop: bool,
+}
+
+impl FeelSettings {
+    pub fn from_args() -> Self {
+        let reduced_motion = std::env::args().any(|arg| arg == "--reduced-motion");
+        FeelSettings {
+            shake: if reduced_motion { 0. } else { 1. },
+            flash: true,
+            hit_stop: !reduced_motion,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Shake {
+    pub trauma: f32,
+}
+
+#[derive(Component)]
+pub struct HitFlash {
+    remaining: f32,
+    original: Handle<ColorMaterial>,
+}
+
+#[derive(Resource)]
+pub struct FlashMaterial(Handle<ColorMaterial>);
+
+#[derive(Resource, Default)]
+pub struct HitStop {
+    until: Option<f32>,
+}
+
+pub fn setup_feel(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    commands.insert_resource(FlashMaterial(
+        materials.add(ColorMaterial::from(Color::WHITE)),
+    ));
+}
+
+// how hard an impact should be felt, between 0 and 1
+struct Impact {
+    entity: Entity,
+    strength: f32,
+    shake: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn feel_impacts(
+    mut hits: EventReader<Hit>,
+    mut booms: EventReader<Boom>,
+    ships: Query<(), With<Ship>>,
+    mut cameras: Query<&mut Shake>,
+    mut flashing: Query<(&mut Handle<ColorMaterial>, Option<&mut HitFlash>)>,
+    mut hit_stop: ResMut<HitStop>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut rapier: ResMut<RapierConfiguration>,
+    real_time: Res<Time<Real>>,
+    flash_material: Res<FlashMaterial>,
+    settings: Res<FeelSettings>,
+    mut commands: Commands,
+) {
+    let impacts = hits
+        .read()
+        .map(|hit| Impact {
+            entity: hit.entity,
+            strength: hit.damage.clamp(0., 1.),
+            shake: ships.contains(hit.entity),
+        })
+        .chain(booms.read().map(|boom| Impact {
+            entity: boom.entity,
+            strength: if ships.contains(boom.entity) {
+                SHIP_BOOM_STRENGTH
+            } else {
+                BOOM_STRENGTH
+            },
+            shake: true,
+        }))
+        .collect::<Vec<_>>();
+
+    // the flash is inserted by a command, a second impact on the same entity this frame
+    // would still find it unflashed and take the flash material for the original one
+    let mut flashed = Vec::new();
+    for impact in impacts {
+        if impact.shake {
+            for mut shake in &mut cameras {
+                shake.trauma = (shake.trauma + impact.strength * settings.shake).min(1.);
+            }
+        }
+        if settings.flash && !flashed.contains(&impact.entity) {
+            flashed.push(impact.entity);
+            if let Ok((mut material, flash)) = flashing.get_mut(impact.entity) {
+                match flash {
+                    Some(mut flash) => flash.remaining = FLASH_TIME,
+                    None => {
+                        let original = std::mem::replace(&mut *material, flash_material.0.clone());
+                        // the entity may have blown up in the same frame, `kill` despawns it
+                        commands.entity(impact.entity).try_insert(HitFlash {
+                            remaining: FLASH_TIME,
+                            original,
+                        });
+                    }
+                }
+            }
+        }
+        if settings.hit_stop && impact.strength >= HIT_STOP_THRESHOLD {
+            let until = real_time.elapsed_seconds() + HIT_STOP_TIME * impact.strength;
+            hit_stop.until = Some(hit_stop.until.map_or(until, |u| u.max(until)));
+            virtual_time.pause();
+            rapier.physics_pipeline_active = false;
+        }
+    }
+}
+
+// runs on real time, the virtual clock is frozen during a hit-stop
+pub fn release_hit_stop(
+    mut hit_stop: ResMut<HitStop>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut rapier: ResMut<RapierConfiguration>,
+    real_time: Res<Time<Real>>,
+) {
+    if hit_stop
+        .until
+        .is_some_and(|until| until <= real_time.elapsed_seconds())
+    {
+        hit_stop.until = None;
+        virtual_time.unpause();
+        rapier.physics_pipeline_active = true;
+    }
+}
+
+pub fn fade_hit_flash(
+    mut flashing: Query<(Entity, &mut HitFlash, &mut Handle<ColorMaterial>)>,
+    real_time: Res<Time<Real>>,
+    mut commands: Commands,
+) {
+    for (entity, mut flash, mut material) in &mut flashing {
+        flash.remaining -= real_time.delta_seconds();
+        if flash.remaining <= 0. {
+            *material = flash.original.clone();
+            commands.entity(entity).remove::<HitFlash>();
+        }
+    }
+}
+
+// runs after `frame_camera`, which resets the camera position every frame
+pub fn shake_camera(
+    mut cameras: Query<(&mut Shake, &mut Transform), With<CameraController>>,
+    real_time: Res<Time<Real>>,
+) {
+    let t = real_time.elapsed_seconds() * SHAKE_FREQUENCY;
+    for (mut shake, mut transform) in &mut cameras {
+        shake.trauma = (shake.trauma - TRAUMA_DECAY * real_time.delta_seconds()).max(0.);
+        let amount = shake.trauma * shake.trauma;
+        // layered sines as cheap smooth noise
+        let offset = Vec2::new(
+            (t * 1.0).sin() + (t * 2.3 + 1.).sin() * 0.5,
+            (t * 1.3 + 2.).sin() + (t * 2.9).sin() * 0.5,
+        ) * MAX_SHAKE_OFFSET
+            * amount;
+        transform.translation += offset.extend(0.);
+        transform.rotation = Quat::from_rotation_z((t * 1.7).sin() * MAX_SHAKE_ANGLE * amount);
+    }
+}
@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+
+// `input` is 1 for turning left, -1 for turning right and 0 when no key is held
+pub fn steer(
+    flight: &Flight,
+    input: f32,
+    velocity: &mut Velocity,
+    impulse: &mut ExternalImpulse,
+    mass: &ReadMassProperties,
+    dt: f32,
+) {
+    match flight.model {
+        FlightModel::Arcade => velocity.angvel = input * flight.turn_rate,
+        FlightModel::Newtonian | FlightModel::Assisted => {
+            impulse.torque_impulse =
+                input * flight.angular_acceleration * mass.principal_inertia * dt;
+        }
+    }
+}
+
+pub fn assist_flight(mut ships: Query<(&Flight, &mut Velocity)>, time: Res<Time>) {
+    let dt = time.delta_seconds();
+    for (flight, mut velocity) in &mut ships {
+        if flight.model != FlightModel::Assisted {
+            continue;
+        }
+        velocity.angvel *= (-flight.rotation_damping * dt).exp();
+        velocity.linvel *= (-flight.linear_drag * dt).exp();
+        if let Some(max_speed) = flight.max_speed {
+            velocity.linvel = velocity.linvel.clamp_length_max(max_speed);
+        }
+    }
+}
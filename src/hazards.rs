@@ -0,0 +1,180 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::collisions::CollisionCategory;
+use crate::components::*;
+use crate::ctf::return_flag;
+use crate::level::*;
+use crate::Boom;
+
+const SWIRL_ARMS: usize = 3;
+const SWIRL_POINTS: usize = 16;
+// how far out the swirl starts, in event horizons
+const SWIRL_REACH: f32 = 3.;
+const SWIRL_SPEED: f32 = 1.5;
+
+pub fn setup_hazards(mut commands: Commands, level: Res<Level>) {
+    for spec in &level.hazards {
+        let (position, mass, radius, category, hazard) = match *spec {
+            HazardSpec::BlackHole {
+                position,
+                mass,
+                horizon,
+            } => (
+                position,
+                mass,
+                horizon,
+                CollisionCategory::Effect,
+                Hazard::BlackHole { horizon },
+            ),
+            HazardSpec::Star {
+                position,
+                mass,
+                size,
+                heat_radius,
+                heat,
+            } => (
+                position,
+                mass,
+                size,
+                CollisionCategory::Hazard,
+                Hazard::Star {
+                    size,
+                    heat_radius,
+                    heat,
+                },
+            ),
+        };
+        // the collider only carries the mass for `apply_gravity`
+        // and, for stars, a surface to crash into
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(0.))),
+            RigidBody::Fixed,
+            Velocity::zero(),
+            Collider::ball(radius),
+            ColliderMassProperties::Mass(mass),
+            ReadMassProperties::default(),
+            ActiveEvents::CONTACT_FORCE_EVENTS,
+            category,
+            hazard,
+        ));
+    }
+}
+
+// bodies that can neither blow up nor expire, parked pool entries are left alone
+type Indestructible = (
+    With<RigidBody>,
+    Without<Hull>,
+    Without<Lifetime>,
+    Without<Flag>,
+    Without<Hazard>,
+    Without<Ghost>,
+    Without<RigidBodyDisabled>,
+);
+
+// everything with a hull goes up in a boom, shots and ore simply expire, flags go home
+// and whatever is left is gone for good
+#[allow(clippy::too_many_arguments)]
+pub fn swallow(
+    holes: Query<(&Transform, &Hazard)>,
+    mut hulls: Query<(Entity, &Transform, &mut Hull), Without<Ghost>>,
+    mut expiring: Query<(&Transform, &mut Lifetime)>,
+    mut flags: Query<(Entity, &Transform, &mut Flag)>,
+    others: Query<(Entity, &Transform), Indestructible>,
+    mut booms: EventWriter<Boom>,
+    mut commands: Commands,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    for (hole_transform, hazard) in &holes {
+        let Hazard::BlackHole { horizon } = *hazard else {
+            continue;
+        };
+        let center = hole_transform.translation.xy();
+        let inside = |transform: &Transform| {
+            level.wrap(transform.translation.xy() - center).length() < horizon
+        };
+        for (entity, transform, mut hull) in &mut hulls {
+            if inside(transform) && hull.integrity >= 0. {
+                hull.integrity = -1.;
+                booms.send(Boom { entity, by: None });
+            }
+        }
+        for (transform, mut lifetime) in &mut expiring {
+            if inside(transform) {
+                lifetime.death = lifetime.death.min(time.elapsed_seconds());
+            }
+        }
+        for (entity, transform, mut flag) in &mut flags {
+            let away = flag.carrier.is_some() || flag.dropped_at.is_some();
+            if away && inside(transform) {
+                return_flag(&mut commands, entity, &mut flag);
+            }
+        }
+        for (entity, transform) in &others {
+            if inside(transform) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+// the heat fades out towards the edge of the radius and hits the shield arc facing the star
+pub fn radiate_heat(
+    stars: Query<(&Transform, &Hazard)>,
+    mut ships: Query<(&Transform, &mut Shield), With<Ship>>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    for (star_transform, hazard) in &stars {
+        let Hazard::Star {
+            size,
+            heat_radius,
+            heat,
+        } = *hazard
+        else {
+            continue;
+        };
+        for (transform, mut shield) in &mut ships {
+            let towards = level.wrap(star_transform.translation.xy() - transform.translation.xy());
+            let distance = (towards.length() - size).max(0.);
+            if distance > heat_radius {
+                continue;
+            }
+            let local = (transform.rotation.inverse() * towards.extend(0.)).xy();
+            let arc = shield.arc_towards(local);
+            let burn = heat * (1. - distance / heat_radius) * time.delta_seconds();
+            shield.absorb(arc, burn);
+        }
+    }
+}
+
+pub fn draw_hazards(hazards: Query<(&Transform, &Hazard)>, mut gizmos: Gizmos, time: Res<Time>) {
+    for (transform, hazard) in &hazards {
+        let center = transform.translation.xy();
+        match *hazard {
+            Hazard::BlackHole { horizon } => {
+                gizmos.circle_2d(center, horizon, Color::WHITE);
+                let spin = time.elapsed_seconds() * SWIRL_SPEED;
+                for arm in 0..SWIRL_ARMS {
+                    let start = spin + arm as f32 * TAU / SWIRL_ARMS as f32;
+                    // each arm winds half a turn inwards from the outer edge to the horizon
+                    let points = (0..=SWIRL_POINTS).map(|i| {
+                        let t = i as f32 / SWIRL_POINTS as f32;
+                        let radius = horizon * (SWIRL_REACH - (SWIRL_REACH - 1.) * t);
+                        center + Vec2::from_angle(start + t * TAU / 2.) * radius
+                    });
+                    gizmos.linestrip_2d(points, Color::rgba(0.7, 0.5, 1., 0.6));
+                }
+            }
+            Hazard::Star {
+                size, heat_radius, ..
+            } => {
+                gizmos.circle_2d(center, size, Color::YELLOW);
+                gizmos.circle_2d(center, size + heat_radius, Color::rgba(1., 0.5, 0., 0.3));
+            }
+        }
+    }
+}
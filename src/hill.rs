@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+use crate::players::*;
+use crate::teams::*;
+
+// the zone is a ring from just above the moon's surface out to a wide orbit
+const HILL_INNER: f32 = 20.;
+const HILL_OUTER: f32 = 160.;
+const CAPTURE_TIME: f32 = 3.;
+const PROGRESS_DECAY: f32 = 0.2;
+const POINTS_PER_SECOND: u32 = 1;
+const PROGRESS_BAR_WIDTH: f32 = 200.;
+
+#[derive(Resource, Default)]
+pub struct Hill {
+    // the team holding the zone scores while it is alone inside
+    pub owner: Option<u8>,
+    // the team working on taking the zone over and how far it got
+    pub capturing: Option<u8>,
+    pub progress: f32,
+    pub contested: bool,
+    // time held since the last point was handed out
+    pub held: f32,
+}
+
+pub fn setup_hill(mut commands: Commands, level: Res<Level>) {
+    if level.rules.mode != GameMode::KingOfTheHill {
+        return;
+    }
+    commands.init_resource::<Hill>();
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|root| {
+            root.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(PROGRESS_BAR_WIDTH),
+                    height: Val::Px(8.),
+                    ..default()
+                },
+                background_color: Color::DARK_GRAY.into(),
+                ..default()
+            })
+            .with_children(|bar| {
+                bar.spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(0.),
+                            height: Val::Percent(100.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    HillProgressBar,
+                ));
+            });
+        });
+}
+
+fn hill_center(moons: &Query<&Transform, With<Moon>>) -> Option<(Vec2, f32)> {
+    moons
+        .iter()
+        .max_by(|a, b| a.scale.x.total_cmp(&b.scale.x))
+        .map(|moon| (moon.translation.xy(), moon.scale.x))
+}
+
+pub fn contest_hill(
+    mut hill: ResMut<Hill>,
+    moons: Query<&Transform, With<Moon>>,
+    ships: Query<(&Ship, &Team, &Transform)>,
+    mut players: ResMut<Players>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    let Some((center, radius)) = hill_center(&moons) else {
+        return;
+    };
+    let inside: Vec<(u8, u8)> = ships
+        .iter()
+        .filter(|(_, _, transform)| {
+            let distance = level.wrap(transform.translation.xy() - center).length();
+            distance > radius + HILL_INNER && distance < radius + HILL_OUTER
+        })
+        .map(|(ship, team, _)| (ship.player, team.id))
+        .collect();
+    let mut teams: Vec<u8> = inside.iter().map(|(_, team)| *team).collect();
+    teams.sort_unstable();
+    teams.dedup();
+
+    let dt = time.delta_seconds();
+    let contested = teams.len() > 1;
+    if hill.contested != contested {
+        hill.contested = contested;
+    }
+    match teams.as_slice() {
+        [] if hill.progress > 0. => {
+            hill.progress = (hill.progress - dt * PROGRESS_DECAY).max(0.);
+        }
+        [team] if hill.owner == Some(*team) => {
+            hill.held += dt;
+            if hill.held >= 1. {
+                hill.held -= 1.;
+                for (player, _) in &inside {
+                    if let Some(state) = players.get_mut(*player) {
+                        state.score += POINTS_PER_SECOND;
+                    }
+                }
+            }
+        }
+        [team] => {
+            // someone else's progress has to drain before this team can start
+            if hill.capturing == Some(*team) {
+                hill.progress += dt / CAPTURE_TIME;
+            } else {
+                hill.progress -= dt / CAPTURE_TIME;
+                if hill.progress <= 0. {
+                    hill.capturing = Some(*team);
+                    hill.progress = 0.;
+                }
+            }
+            if hill.progress >= 1. {
+                hill.owner = Some(*team);
+                hill.capturing = None;
+                hill.progress = 0.;
+                hill.held = 0.;
+            }
+        }
+        // contested or empty, nothing moves until one team is left
+        _ => {}
+    }
+}
+
+pub fn draw_hill(
+    hill: Res<Hill>,
+    moons: Query<&Transform, With<Moon>>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+) {
+    let Some((center, radius)) = hill_center(&moons) else {
+        return;
+    };
+    let color = if hill.contested && (time.elapsed_seconds() * 6.).sin() > 0. {
+        Color::WHITE
+    } else {
+        hill.owner.map_or(Color::GRAY, team_color)
+    };
+    gizmos.circle_2d(center, radius + HILL_INNER, color);
+    gizmos.circle_2d(center, radius + HILL_OUTER, color);
+}
+
+pub fn update_hill_progress(
+    hill: Res<Hill>,
+    mut bars: Query<(&mut Style, &mut BackgroundColor), With<HillProgressBar>>,
+) {
+    if !hill.is_changed() {
+        return;
+    }
+    for (mut style, mut background) in &mut bars {
+        style.width = Val::Percent(100. * hill.progress.clamp(0., 1.));
+        *background = hill.capturing.map_or(Color::GRAY, team_color).into();
+    }
+}
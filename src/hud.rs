@@ -0,0 +1,315 @@
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+use crate::players::*;
+use crate::teams::*;
+
+const BAR_WIDTH: f32 = 120.;
+const BAR_HEIGHT: f32 = 6.;
+const MARKER_MARGIN: f32 = 12.;
+
+#[derive(Resource)]
+pub struct ShowLabels(pub bool);
+
+impl Default for ShowLabels {
+    fn default() -> Self {
+        ShowLabels(true)
+    }
+}
+
+pub fn setup_hud(mut commands: Commands, asset_server: Res<AssetServer>, level: Res<Level>) {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+        font_size: 14.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::SpaceBetween,
+                padding: UiRect::all(Val::Px(8.)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|root| {
+            for spawn in &level.spawns {
+                let text_style = TextStyle {
+                    color: team_color(spawn.team()),
+                    ..text_style.clone()
+                };
+                spawn_player_panel(root, spawn.player, &text_style);
+            }
+        });
+
+    if level.has_teams() {
+        commands.spawn((
+            TextBundle::from_section("", text_style.clone()).with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(8.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            }),
+            TeamScoreText,
+        ));
+    }
+
+    for spawn in &level.spawns {
+        commands.spawn((
+            TextBundle::from_section(format!("P{}", spawn.player + 1), text_style.clone())
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                }),
+            OffscreenMarker {
+                player: spawn.player,
+            },
+        ));
+    }
+}
+
+fn spawn_player_panel(root: &mut ChildBuilder, player: u8, text_style: &TextStyle) {
+    root.spawn(NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.),
+            ..default()
+        },
+        ..default()
+    })
+    .with_children(|panel| {
+        panel.spawn((
+            TextBundle::from_section("", text_style.clone()),
+            ScoreText { player },
+        ));
+        spawn_bar(panel, player, HudBarKind::Shield, Color::RED);
+        spawn_bar(panel, player, HudBarKind::Hull, Color::WHITE);
+        spawn_bar(panel, player, HudBarKind::Cooldown, Color::YELLOW);
+        spawn_bar(panel, player, HudBarKind::Fuel, Color::CYAN);
+        panel.spawn((
+            TextBundle::from_section("", text_style.clone()),
+            PowerText { player },
+        ));
+        panel.spawn((
+            TextBundle::from_section("", text_style.clone()),
+            CargoText { player },
+        ));
+    });
+}
+
+fn spawn_bar(panel: &mut ChildBuilder, player: u8, kind: HudBarKind, color: Color) {
+    panel
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(BAR_WIDTH),
+                height: Val::Px(BAR_HEIGHT),
+                ..default()
+            },
+            background_color: Color::DARK_GRAY.into(),
+            ..default()
+        })
+        .with_children(|bar| {
+            bar.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(0.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+                HudBar { player, kind },
+            ));
+        });
+}
+
+pub fn update_hud_bars(
+    ships: Query<(&Ship, &Shield, &Hull, &Gun, &Thruster), Without<Ghost>>,
+    mut bars: Query<(&HudBar, &mut Style)>,
+) {
+    for (bar, mut style) in &mut bars {
+        let fill = ships
+            .iter()
+            .find(|(ship, ..)| ship.player == bar.player)
+            .map_or(0., |(_, shield, hull, gun, thruster)| match bar.kind {
+                HudBarKind::Shield => shield.fraction(),
+                HudBarKind::Hull => hull.fraction(),
+                HudBarKind::Cooldown => 1. - gun.time / gun.cooldown,
+                HudBarKind::Fuel => thruster.fuel,
+            });
+        let width = Val::Percent(100. * fill.clamp(0., 1.));
+        if style.width != width {
+            style.width = width;
+        }
+    }
+}
+
+// one bar per pip, dots for the pips routed elsewhere
+pub fn update_power_text(
+    ships: Query<(&Ship, &Reactor), Without<Ghost>>,
+    mut texts: Query<(&PowerText, &mut Text)>,
+) {
+    for (power_text, mut text) in &mut texts {
+        let Some((_, reactor)) = ships
+            .iter()
+            .find(|(ship, _)| ship.player == power_text.player)
+        else {
+            continue;
+        };
+        let pips = |system: PowerSystem| {
+            let pips = reactor.pips[system.index()] as usize;
+            "|".repeat(pips) + &".".repeat(Reactor::MAX_PIPS as usize - pips)
+        };
+        let value = format!(
+            "shields {}  weapons {}  engines {}",
+            pips(PowerSystem::Shields),
+            pips(PowerSystem::Weapons),
+            pips(PowerSystem::Engines)
+        );
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+pub fn update_score_text(
+    players: Res<Players>,
+    mut texts: Query<(&ScoreText, &mut Text)>,
+    mut team_texts: Query<&mut Text, (With<TeamScoreText>, Without<ScoreText>)>,
+) {
+    if !players.is_changed() {
+        return;
+    }
+    for mut text in &mut team_texts {
+        let mut teams: Vec<u8> = players.players.iter().map(|p| p.team).collect();
+        teams.sort_unstable();
+        teams.dedup();
+        text.sections[0].value = teams
+            .iter()
+            .map(|team| format!("team {}: {}", team + 1, players.team_score(*team)))
+            .collect::<Vec<_>>()
+            .join("    ");
+    }
+    for (score_text, mut text) in &mut texts {
+        if let Some(state) = players.get(score_text.player) {
+            text.sections[0].value = if players.team_lives.is_some() {
+                format!("P{}  score {}", state.player + 1, state.score)
+            } else {
+                format!(
+                    "P{}  score {}  lives {}",
+                    state.player + 1,
+                    state.score,
+                    state.lives
+                )
+            };
+        }
+    }
+}
+
+pub fn update_offscreen_markers(
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    ships: Query<(&Ship, &GlobalTransform), Without<Ghost>>,
+    mut markers: Query<(&OffscreenMarker, &mut Style, &mut Visibility)>,
+) {
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(size) = camera.logical_viewport_size() else {
+        return;
+    };
+    for (marker, mut style, mut visibility) in &mut markers {
+        let position = ships
+            .iter()
+            .find(|(ship, _)| ship.player == marker.player)
+            .and_then(|(_, transform)| {
+                camera.world_to_viewport(camera_transform, transform.translation())
+            });
+        match position {
+            Some(position) if !Rect::from_corners(Vec2::ZERO, size).contains(position) => {
+                let clamped = position.clamp(
+                    Vec2::splat(MARKER_MARGIN),
+                    size - Vec2::splat(2. * MARKER_MARGIN),
+                );
+                style.left = Val::Px(clamped.x);
+                style.top = Val::Px(clamped.y);
+                *visibility = Visibility::Inherited;
+            }
+            _ => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+pub fn toggle_labels(keyboard_input: Res<ButtonInput<KeyCode>>, mut show: ResMut<ShowLabels>) {
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        show.0 = !show.0;
+    }
+}
+
+pub fn init_energy_display(
+    ships: Query<(Entity, &Transform, Has<Ghost>), Added<Ship>>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 10.0,
+        color: Color::WHITE,
+    };
+    for (ship, &transform, ghost) in &ships {
+        if ghost {
+            continue;
+        }
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section("", text_style.clone()),
+                transform,
+                ..default()
+            },
+            EnergyDisplay { ship },
+        ));
+    }
+}
+
+pub fn arrange_energy_display(
+    ships: Query<(&Transform, &Shield, &Hull), With<Ship>>,
+    mut displays: Query<
+        (
+            Entity,
+            &mut Transform,
+            &EnergyDisplay,
+            &mut Text,
+            &mut Visibility,
+        ),
+        Without<Ship>,
+    >,
+    show: Res<ShowLabels>,
+    mut commands: Commands,
+) {
+    for (display_entity, mut transform, display, mut text, mut visibility) in &mut displays {
+        let Ok((ship_transform, shield, hull)) = ships.get(display.ship) else {
+            commands.entity(display_entity).despawn();
+            continue;
+        };
+        *visibility = if show.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        transform.translation = ship_transform.translation + Vec3::new(-20., 30., 0.);
+        let value = format!(
+            "{:0} % / {:0} %",
+            (100. * shield.fraction()) as i32,
+            (100. * hull.fraction().max(0.)) as i32
+        );
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use std::error::Error;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+const LEVEL_DIR: &str = "assets/levels";
+const DEFAULT_LEVEL: &str = "classic";
+const TEAM_SPACING: f32 = 60.;
+
+#[derive(Resource, Deserialize, Clone)]
+pub struct Level {
+    pub name: String,
+    pub space_size: f32,
+    #[serde(default)]
+    pub moons: Vec<MoonSpec>,
+    #[serde(default)]
+    pub asteroid_fields: Vec<AsteroidField>,
+    // black holes and stars, they attract like moons but never move
+    #[serde(default)]
+    pub hazards: Vec<HazardSpec>,
+    pub spawns: Vec<SpawnPoint>,
+    // capture the flag bases, teams without one get a base where their ships start
+    #[serde(default)]
+    pub bases: Vec<BaseSpec>,
+    // race gates in the order they have to be passed, the first one is start and finish
+    #[serde(default)]
+    pub checkpoints: Vec<CheckpointSpec>,
+    #[serde(default)]
+    pub rules: Rules,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MoonSpec {
+    pub position: Vec2,
+    #[serde(default)]
+    pub velocity: Vec2,
+    pub size: f32,
+    // when no mass is given the moon gets its mass from its size
+    #[serde(default)]
+    pub mass: Option<f32>,
+    #[serde(default)]
+    pub orbit: OrbitSpec,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub enum OrbitSpec {
+    // drifts with its `velocity` under gravity
+    #[default]
+    Free,
+    // pinned in place, still attracts everything else
+    Fixed,
+    // gets the velocity for a circular orbit around the other moons once its mass is known
+    Circular,
+    // kinematic rail: circles `center` (or moon number `around`) through its start position
+    Rail {
+        #[serde(default)]
+        around: Option<usize>,
+        #[serde(default)]
+        center: Vec2,
+        period: f32,
+    },
+}
+
+#[derive(Deserialize, Clone)]
+pub enum HazardSpec {
+    // swallows anything that crosses the event horizon
+    BlackHole {
+        position: Vec2,
+        mass: f32,
+        horizon: f32,
+    },
+    // a solid body that burns down the shields of ships within `heat_radius`,
+    // `heat` is the shield drain per second right at its surface
+    Star {
+        position: Vec2,
+        mass: f32,
+        size: f32,
+        heat_radius: f32,
+        heat: f32,
+    },
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AsteroidField {
+    pub center: Vec2,
+    pub extent: Vec2,
+    pub count: u32,
+    pub size: f32,
+    #[serde(default)]
+    pub speed: f32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct CheckpointSpec {
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BaseSpec {
+    pub team: u8,
+    pub position: Vec2,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SpawnPoint {
+    pub player: u8,
+    pub position: Vec2,
+    #[serde(default)]
+    pub rotation: f32,
+    // players without a team fight on their own
+    #[serde(default)]
+    pub team: Option<u8>,
+}
+
+impl SpawnPoint {
+    pub fn team(&self) -> u8 {
+        self.team.unwrap_or(self.player)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Rules {
+    pub gravity_scale: f32,
+    pub heal_speed: f32,
+    pub collision_damage: f32,
+    pub lives: u32,
+    pub laps: u32,
+    // fuel is a fraction of a full tank, burnt and regained per second
+    pub fuel_burn: f32,
+    pub fuel_regen: f32,
+    pub refuel_speed: f32,
+    // overrides the flight model of every ship class
+    pub flight_model: Option<FlightModel>,
+    pub mode: GameMode,
+    pub friendly_fire: FriendlyFire,
+    pub spawn_layout: SpawnLayout,
+    pub shields: ShieldLayout,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum FriendlyFire {
+    Off,
+    Reduced,
+    #[default]
+    Full,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum SpawnLayout {
+    // every ship starts where its spawn point says
+    #[default]
+    AsWritten,
+    // teams start on opposite sides of the arena with teammates next to each other
+    TeamSides,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ShieldLayout {
+    // one shield around the whole ship
+    #[default]
+    Bubble,
+    // separate front and rear arcs, hits drain the arc facing them
+    FrontRear,
+}
+
+impl ShieldLayout {
+    pub fn arcs(self) -> usize {
+        match self {
+            ShieldLayout::Bubble => 1,
+            ShieldLayout::FrontRear => 2,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum GameMode {
+    // every player for themselves, each with their own lives
+    #[default]
+    Versus,
+    // the players team up against waves of enemy fighters and share their lives
+    Survival,
+    // teams score for every second they hold the zone around the biggest moon on their own
+    KingOfTheHill,
+    // teams score by towing the other team's flag back to their own base
+    CaptureTheFlag,
+    // laps through the checkpoints, against each other and the ghost of the best lap
+    Race,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum FlightModel {
+    // turning sets the spin directly and stops as soon as the key is released
+    Arcade,
+    // turning applies torque, the ship keeps spinning until countered
+    Newtonian,
+    // torque like newtonian, with spin damping, drag and a speed cap
+    Assisted,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            gravity_scale: 0.3,
+            heal_speed: 0.2,
+            collision_damage: 0.2,
+            lives: 3,
+            laps: 3,
+            fuel_burn: 0.25,
+            fuel_regen: 0.02,
+            refuel_speed: 0.5,
+            flight_model: None,
+            mode: GameMode::Versus,
+            friendly_fire: FriendlyFire::Full,
+            spawn_layout: SpawnLayout::AsWritten,
+            shields: ShieldLayout::Bubble,
+        }
+    }
+}
+
+impl Level {
+    // the arena is a torus, anything leaving one side comes back in on the other
+    pub fn wrap(&self, mut position: Vec2) -> Vec2 {
+        let space_size = self.space_size;
+        if position.x < -space_size {
+            position.x += 2. * space_size;
+        }
+        if position.y < -space_size {
+            position.y += 2. * space_size;
+        }
+        if position.x > space_size {
+            position.x -= 2. * space_size;
+        }
+        if position.y > space_size {
+            position.y -= 2. * space_size;
+        }
+        position
+    }
+
+    pub fn load(path: &Path) -> Result<Level, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)?;
+        let mut level: Level = ron::from_str(&source)?;
+        level.validate()?;
+        if level.rules.spawn_layout == SpawnLayout::TeamSides {
+            level.arrange_teams();
+        }
+        Ok(level)
+    }
+
+    // catches what the format allows but the game cannot run
+    fn validate(&self) -> Result<(), String> {
+        for (index, moon) in self.moons.iter().enumerate() {
+            if let OrbitSpec::Rail { around, period, .. } = moon.orbit {
+                if period <= 0. {
+                    return Err(format!("moon {index}: rail period must be positive"));
+                }
+                if around.is_some_and(|around| around >= self.moons.len() || around == index) {
+                    return Err(format!(
+                        "moon {index}: rail around itself or an unknown moon"
+                    ));
+                }
+            }
+        }
+        // with a single gate every pass would finish a lap
+        if self.rules.mode == GameMode::Race && self.checkpoints.len() < 2 {
+            return Err("a race needs at least two checkpoints".into());
+        }
+        Ok(())
+    }
+
+    pub fn has_teams(&self) -> bool {
+        self.spawns.iter().any(|spawn| spawn.team.is_some())
+    }
+
+    // spreads the teams evenly around the center, each facing inwards in a line abreast
+    fn arrange_teams(&mut self) {
+        let mut teams: Vec<u8> = self.spawns.iter().map(SpawnPoint::team).collect();
+        teams.sort_unstable();
+        teams.dedup();
+        for (index, team) in teams.iter().enumerate() {
+            let angle = PI + index as f32 * TAU / teams.len() as f32;
+            let direction = Vec2::from_angle(angle);
+            let anchor = direction * self.space_size * 0.5;
+            let members: Vec<&mut SpawnPoint> = self
+                .spawns
+                .iter_mut()
+                .filter(|spawn| spawn.team() == *team)
+                .collect();
+            let count = members.len();
+            for (slot, spawn) in members.into_iter().enumerate() {
+                let offset = (slot as f32 - (count - 1) as f32 / 2.) * TEAM_SPACING;
+                spawn.position = anchor + direction.perp() * offset;
+                spawn.rotation = angle + FRAC_PI_2;
+            }
+        }
+    }
+}
+
+// `--level classic` looks in assets/levels, `--level some/file.ron` is taken as is
+pub fn level_path_from_args() -> PathBuf {
+    let mut args = std::env::args().skip(1);
+    let mut name = DEFAULT_LEVEL.to_string();
+    while let Some(arg) = args.next() {
+        if arg == "--level" {
+            if let Some(value) = args.next() {
+                name = value;
+            }
+        } else if let Some(value) = arg.strip_prefix("--level=") {
+            name = value.to_string();
+        }
+    }
+    let path = PathBuf::from(&name);
+    if path.extension().is_some() {
+        path
+    } else {
+        Path::new(LEVEL_DIR).join(name).with_extension("ron")
+    }
+}
@@ -1,8 +1,34 @@
+mod audio;
+mod camera;
+mod collisions;
 mod components;
+mod craters;
+mod ctf;
+mod feel;
+mod flight;
+mod hazards;
+mod hill;
+mod hud;
+mod level;
+mod mining;
+mod orbit;
+mod particles;
+mod players;
+mod pool;
+mod race;
+mod radar;
+mod ship_class;
+mod survival;
+mod synth;
+mod teams;
+mod trajectory;
 
 use std::f32::consts::PI;
 use std::ops::Mul;
 
+use bevy::ecs::system::SystemParam;
+use bevy::input::gamepad::GamepadConnectionEvent;
+use bevy::input::InputSystem;
 use bevy::prelude::*;
 
 use bevy::sprite::MaterialMesh2dBundle;
@@ -12,32 +38,226 @@ use bevy::render::{
 };
 use bevy_rapier2d::prelude::*;
 
+use crate::audio::*;
+use crate::camera::*;
+use crate::collisions::*;
 use crate::components::*;
-
-const HEAL_SPEED: f32 = 0.2;
+use crate::craters::*;
+use crate::ctf::*;
+use crate::feel::*;
+use crate::flight::*;
+use crate::hazards::*;
+use crate::hill::*;
+use crate::hud::*;
+use crate::level::*;
+use crate::mining::*;
+use crate::orbit::*;
+use crate::particles::*;
+use crate::players::*;
+use crate::pool::*;
+use crate::race::*;
+use crate::radar::*;
+use crate::ship_class::*;
+use crate::survival::*;
+use crate::teams::*;
+use crate::trajectory::*;
 
 fn main() {
+    let level_path = level_path_from_args();
+    let level = Level::load(&level_path)
+        .unwrap_or_else(|err| panic!("could not load level {}: {}", level_path.display(), err));
+
+    let audio_backend = audio_backend_from_args();
+    let mut plugins = DefaultPlugins.build();
+    if audio_backend == AudioBackend::Null {
+        plugins = plugins.disable::<bevy::audio::AudioPlugin>();
+    }
+
     App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
-                        .add_plugins(RapierDebugRenderPlugin::default())
+        .add_plugins(plugins)
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(
+            PIXELS_PER_METER,
+        ))
+        .add_plugins(RapierDebugRenderPlugin::default())
+        .insert_resource(Players::from_level(&level))
+        .insert_resource(CollisionMatrix::for_mode(level.rules.mode))
+        .insert_resource(level)
+        .init_resource::<ShowOrbits>()
+        .init_resource::<ShowLabels>()
+        .init_resource::<RadarSettings>()
+        .init_resource::<EntityPool<Shot>>()
+        .insert_resource(audio_backend)
+        .insert_resource(FeelSettings::from_args())
+        .init_resource::<HitStop>()
+        .init_resource::<GamepadSlots>()
         .add_event::<Boom>()
-        .add_systems(Startup, setupv3)
-        .add_systems(Update, input_handler)
-        .add_systems(Update, kill_debris)
-        .add_systems(Update, (apply_gravity, check_collisions, kill))
+        .add_event::<Hit>()
+        .add_event::<ShotFired>()
+        .add_event::<PlaySound>()
+        .add_systems(
+            Startup,
+            (
+                setupv3,
+                setup_hud,
+                setup_class_selection,
+                setup_survival,
+                setup_hill,
+                setup_ctf,
+                setup_race,
+                setup_hazards,
+                setup_particles,
+                setup_feel,
+            ),
+        )
+        .add_systems(Startup, setup_sounds.run_if(audio_device))
+        .add_systems(PreUpdate, track_gamepads.after(InputSystem))
+        .add_systems(
+            Update,
+            (
+                player_controls.before(input_handler),
+                steer_npcs.before(input_handler),
+                input_handler,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                run_waves.run_if(not(resource_exists::<ClassSelection>)),
+                update_wave_text,
+            )
+                .run_if(resource_exists::<Survival>),
+        )
+        .add_systems(
+            Update,
+            (contest_hill, draw_hill, update_hill_progress).run_if(resource_exists::<Hill>),
+        )
+        .add_systems(
+            Update,
+            (
+                touch_flags,
+                drop_flags.after(DealDamage).before(kill),
+                keep_flags_with_carriers.after(warp_space),
+                capture_flags,
+                draw_bases,
+            )
+                .run_if(resource_exists::<Ctf>),
+        )
+        .add_systems(
+            Update,
+            (
+                join_race,
+                track_laps.after(player_controls).before(input_handler),
+                replay_ghost.after(track_laps).before(input_handler),
+                draw_checkpoints,
+                update_race_text,
+            )
+                .run_if(resource_exists::<Race>),
+        )
+        .add_systems(
+            Update,
+            (select_classes, update_class_choice_text).run_if(resource_exists::<ClassSelection>),
+        )
+        .add_systems(
+            Update,
+            (kill_debris, recycle_expired::<Shot>.before(input_handler)),
+        )
+        .add_systems(
+            Update,
+            (
+                apply_gravity,
+                check_collisions.in_set(DealDamage),
+                kill.after(DealDamage),
+                carve_craters.after(DealDamage).before(kill),
+            ),
+        )
+        .add_systems(
+            Update,
+            (swallow.in_set(DealDamage), radiate_heat, draw_hazards),
+        )
+        .add_systems(
+            PostUpdate,
+            apply_collision_groups.before(PhysicsSet::SyncBackend),
+        )
+        .add_systems(
+            Update,
+            (score_kills.after(DealDamage).before(kill), respawn_ships),
+        )
+        .add_systems(
+            Update,
+            (
+                drop_ore.after(DealDamage).before(kill),
+                collect_ore,
+                deliver_ore,
+                apply_upgrades.after(deliver_ore),
+                update_cargo_text,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                emit_exhaust,
+                emit_explosions.after(DealDamage).before(kill),
+                update_particles,
+            ),
+        )
+        .add_systems(Update, queue_sounds)
+        .add_systems(
+            Update,
+            (play_sounds, attach_engine_sound, engine_sound).run_if(audio_device),
+        )
+        .add_systems(Update, log_sounds.run_if(not(audio_device)))
         .add_systems(Update, warp_space)
-        .add_systems(Update, load_shield)
+        .add_systems(Update, (toggle_camera_mode, frame_camera.after(warp_space)))
+        .add_systems(
+            Update,
+            (
+                feel_impacts.after(check_collisions),
+                release_hit_stop,
+                fade_hit_flash,
+                shake_camera.after(frame_camera),
+            ),
+        )
+        .add_systems(Update, (toggle_radar, draw_radar.after(shake_camera)))
+        .add_systems(Update, (launch_circular_orbits, follow_rails))
+        .add_systems(Update, (toggle_orbits, draw_orbits))
+        .add_systems(Update, (toggle_trajectories, draw_trajectories))
+        .add_systems(Update, (load_shield, load_fuel.after(input_handler)))
+        .add_systems(Update, assist_flight.after(input_handler))
         .add_systems(Update, copy_shield_value)
-        .add_systems(Update, init_energy_display)
-        .add_systems(Update, arrange_energy_display)
+        .add_systems(
+            Update,
+            (init_energy_display, arrange_energy_display, toggle_labels),
+        )
+        .add_systems(
+            Update,
+            (
+                update_hud_bars,
+                update_score_text,
+                update_power_text,
+                update_offscreen_markers,
+            ),
+        )
         .run();
 }
 #[derive(Event)]
 struct Boom {
     entity: Entity,
+    by: Option<u8>,
+}
+
+// the systems that send `Boom`, its readers run after them and before `kill` despawns
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct DealDamage;
+
+#[derive(Event)]
+struct Hit {
+    entity: Entity,
+    damage: f32,
 }
 
+#[derive(Event)]
+struct ShotFired;
+
 fn create_ship() -> Vec<Vec3> {
     vec![
         Vec3::new(0.0, 0.7, 0.0),
@@ -93,6 +313,15 @@ fn create_debris() -> Vec<Vec3> {
     ]
 }
 
+fn create_flag() -> Vec<Vec3> {
+    vec![
+        Vec3::new(-0.5, -1.0, 0.0),
+        Vec3::new(-0.5, 1.0, 0.0),
+        Vec3::new(0.7, 0.5, 0.0),
+        Vec3::new(-0.5, 0.0, 0.0),
+    ]
+}
+
 fn create_shot() -> Vec<Vec3> {
     vec![
         Vec3::new(0.0, 0.4, 0.0),
@@ -102,7 +331,8 @@ fn create_shot() -> Vec<Vec3> {
     ]
 }
 
-const MOON_TILES: usize = 15;
+// also the number of points on a moon's surface that craters can dig into
+const MOON_TILES: usize = 48;
 fn create_moon() -> Vec<Vec3> {
     (0..MOON_TILES)
         .map(|i| Vec2::from_angle(i as f32 * PI * 2.0 / MOON_TILES as f32).extend(0.))
@@ -117,11 +347,39 @@ fn create_shield() -> Vec<Vec3> {
         .collect()
 }
 
+// half rings with a small gap at both ends, forward is +y
+fn create_shield_arc(from: f32) -> Vec<Vec3> {
+    let segments = 8;
+    let gap = 0.15;
+    (0..=segments)
+        .map(|i| {
+            Vec2::from_angle(from + gap + (PI - 2. * gap) * i as f32 / segments as f32).extend(0.)
+        })
+        .collect()
+}
+
+fn create_shield_front() -> Vec<Vec3> {
+    create_shield_arc(0.)
+}
+
+fn create_shield_rear() -> Vec<Vec3> {
+    create_shield_arc(PI)
+}
+
 fn create_mesh(geometry: fn() -> Vec<Vec3>, scale: f32) -> Mesh {
-    let lines: Vec<Vec3> = geometry().iter().map(|v| v.mul(scale)).collect();
+    line_mesh(geometry().iter().map(|v| v.mul(scale)).collect(), true)
+}
+
+fn create_open_mesh(geometry: fn() -> Vec<Vec3>, scale: f32) -> Mesh {
+    line_mesh(geometry().iter().map(|v| v.mul(scale)).collect(), false)
+}
+
+fn line_mesh(lines: Vec<Vec3>, closed: bool) -> Mesh {
     let len = lines.len();
     let mut indexes: Vec<u32> = (0..(len as u32)).collect();
-    indexes.push(0);
+    if closed {
+        indexes.push(0);
+    }
     /* //![0, 1, 2, 3, 0])) */
     Mesh::new(
         PrimitiveTopology::LineStrip,
@@ -171,121 +429,246 @@ impl Default for Celestial {
 struct MeshHandles {
     ship: Handle<Mesh>,
     fighter: Handle<Mesh>,
-    debris: Handle<Mesh>,
+    heavy: Handle<Mesh>,
     shot: Handle<Mesh>,
     asteroid: Handle<Mesh>,
     shield: Handle<Mesh>,
+    shield_front: Handle<Mesh>,
+    shield_rear: Handle<Mesh>,
     moon: Handle<Mesh>,
+    ore: Handle<Mesh>,
 
     material: Handle<ColorMaterial>,
     shot_material: Handle<ColorMaterial>,
-    debris_material: Handle<ColorMaterial>,
+    // indexed by `Ore::index`, tints the asteroids and their ore
+    ore_materials: Vec<Handle<ColorMaterial>>,
 }
 
 fn setupv3(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    level: Res<Level>,
 ) {
     let mesh_handles = MeshHandles {
         ship: meshes.add(create_mesh(create_ship, 16.)),
-        fighter: meshes.add(create_mesh(create_figter, 32.)),
-        debris: meshes.add(create_mesh(create_debris, 16.)),
+        fighter: meshes.add(create_mesh(create_figter, 48.)),
+        heavy: meshes.add(create_mesh(create_ship, 24.)),
         shot: meshes.add(create_mesh(create_shot, 16.)),
         asteroid: meshes.add(create_mesh(create_asteroid, 8.)),
         shield: meshes.add(create_mesh(create_shield, 16.)),
+        shield_front: meshes.add(create_open_mesh(create_shield_front, 16.)),
+        shield_rear: meshes.add(create_open_mesh(create_shield_rear, 16.)),
         moon: meshes.add(create_mesh(create_moon, 1.)),
+        ore: meshes.add(create_mesh(create_debris, 12.)),
 
         material: materials.add(ColorMaterial::from(Color::BLUE)),
         shot_material: materials.add(ColorMaterial::from(Color::RED)),
-        debris_material: materials.add(ColorMaterial::from(Color::GRAY)),
+        ore_materials: Ore::ALL
+            .iter()
+            .map(|ore| materials.add(ColorMaterial::from(ore_color(*ore))))
+            .collect(),
     };
 
-    commands.spawn(Camera2dBundle::default());
+    commands.spawn((
+        Camera2dBundle::default(),
+        CameraController::default(),
+        Shake::default(),
+    ));
+
+    info!("starting level {}", level.name);
 
-    for (i, pos) in vec![Vec3::new(-100., 0., 0.), Vec3::new(100., 0., 0.)]
+    let moons: Vec<Entity> = level
+        .moons
         .iter()
-        .enumerate()
-    {
-        commands
-            .spawn((
-                MaterialMesh2dBundle {
-                    mesh: mesh_handles.ship.clone().into(),
-                    transform: Transform::default().with_translation(*pos),
-                    material: mesh_handles.material.clone(),
-                    ..Default::default()
+        .map(|moon| {
+            spawn_moon(
+                &mut commands,
+                moon.position.extend(0.),
+                Velocity {
+                    linvel: moon.velocity,
+                    angvel: 0.,
                 },
-                Collider::ball(16.0),
-                Celestial {
-                    ..Celestial::default()
+                &mesh_handles,
+                moon.size,
+                moon.mass,
+                match moon.orbit {
+                    OrbitSpec::Free | OrbitSpec::Circular => RigidBody::Dynamic,
+                    OrbitSpec::Fixed => RigidBody::Fixed,
+                    OrbitSpec::Rail { .. } => RigidBody::KinematicPositionBased,
                 },
-                Ship { player: i as u8 },
-                Shield { energy: 1.0 },
-                Thruster { thruster_time: 0. },
-                Gun { time: 0. },
-            ))
-            .with_children(|p| {
-                p.spawn((
-                    MaterialMesh2dBundle {
-                        mesh: mesh_handles.shield.clone().into(),
-                        transform: Transform::default(),
-                        material: materials.add(ColorMaterial::from(Color::RED)),
-                        ..Default::default()
-                    },
-                    Shield { energy: 1.0 },
-                ));
-            });
-    }
+            )
+        })
+        .collect();
 
-    spawn_moon(
-        &mut commands,
-        Vec3::ZERO, 
-        Velocity { linvel: Vec2::ZERO, angvel: 0. , },
-        &mesh_handles,
-        32.
-    );
+    for (moon, &entity) in level.moons.iter().zip(&moons) {
+        match moon.orbit {
+            OrbitSpec::Circular => {
+                commands.entity(entity).insert(CircularOrbit);
+            }
+            OrbitSpec::Rail {
+                around,
+                center,
+                period,
+            } => {
+                let center = around.map_or(center, |i| level.moons[i].position);
+                let offset = moon.position - center;
+                commands.entity(entity).insert(Rail {
+                    around: around.map(|i| moons[i]),
+                    center,
+                    radius: offset.length(),
+                    phase: offset.y.atan2(offset.x),
+                    angular_speed: 2. * PI / period,
+                });
+            }
+            OrbitSpec::Free | OrbitSpec::Fixed => {}
+        }
+    }
 
-    for _ in 0..4 {
-        let pos = Vec3::new(
-            rand::random::<f32>() * 100. - 50.,
-            rand::random::<f32>() * 100. - 50.,
-            0.,
-        );
-        spawn_asteroid(
-            &mut commands,
-            pos,
-            &mesh_handles,
-            4.,
-            Velocity {
-                linvel: Vec2::ZERO,
-                angvel: 0.,
-            },
-        );
+    for field in &level.asteroid_fields {
+        for _ in 0..field.count {
+            let pos = field.center
+                + Vec2::new(
+                    rand::random::<f32>() * 2. - 1.,
+                    rand::random::<f32>() * 2. - 1.,
+                ) * field.extent;
+            let direction = Vec2::from_angle(rand::random::<f32>() * PI * 2.);
+            spawn_asteroid(
+                &mut commands,
+                pos.extend(0.),
+                &mesh_handles,
+                field.size,
+                Velocity {
+                    linvel: direction * field.speed,
+                    angvel: 0.,
+                },
+                random_ore(),
+            );
+        }
     }
     commands.insert_resource(mesh_handles);
 }
 
-fn spawn_moon(commands: &mut Commands, pos: Vec3, velocity: Velocity, mesh_handles: &MeshHandles, size: f32) {
-    commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: mesh_handles.moon.clone().into(),
-            transform: Transform::default()
-                .with_translation(pos)
-                .with_scale(Vec3::splat(size)),
-            material: mesh_handles.material.clone(),
-            ..Default::default()
-        },
-        Celestial {
-            velocity,
-            ..Default::default()
-        },
-        Collider::ball(1.),
-        ColliderMassProperties::Density(6.0),
-        //ReadMassProperties { 
-        //},
-        Shield { energy: 10000. },
-        Moon {},
-    ));
+fn spawn_moon(
+    commands: &mut Commands,
+    pos: Vec3,
+    velocity: Velocity,
+    mesh_handles: &MeshHandles,
+    size: f32,
+    mass: Option<f32>,
+    body: RigidBody,
+) -> Entity {
+    commands
+        .spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh_handles.moon.clone().into(),
+                transform: Transform::default()
+                    .with_translation(pos)
+                    .with_scale(Vec3::splat(size)),
+                material: mesh_handles.material.clone(),
+                ..Default::default()
+            },
+            Celestial {
+                velocity,
+                body,
+                ..Default::default()
+            },
+            Collider::ball(1.),
+            match mass {
+                Some(mass) => ColliderMassProperties::Mass(mass),
+                None => ColliderMassProperties::Density(6.0),
+            },
+            Hull::new(10000.),
+            MoonSurface::new(MOON_TILES),
+            CollisionCategory::Moon,
+            Moon {},
+        ))
+        .id()
+}
+
+fn spawn_ship(
+    commands: &mut Commands,
+    mesh_handles: &MeshHandles,
+    materials: &mut Assets<ColorMaterial>,
+    spawn: &SpawnPoint,
+    class: ShipClass,
+    rules: &Rules,
+) -> Entity {
+    let stats = class.stats();
+    commands
+        .spawn((
+            MaterialMesh2dBundle {
+                mesh: class.mesh(mesh_handles).into(),
+                transform: Transform::default()
+                    .with_translation(spawn.position.extend(0.))
+                    .with_rotation(Quat::from_rotation_z(spawn.rotation)),
+                material: materials.add(ColorMaterial::from(team_color(spawn.team()))),
+                ..Default::default()
+            },
+            Collider::ball(stats.radius),
+            ColliderMassProperties::Density(stats.density),
+            Celestial {
+                ..Celestial::default()
+            },
+            (
+                Ship {
+                    player: spawn.player,
+                },
+                CollisionCategory::Ship,
+                Team { id: spawn.team() },
+            ),
+            Shield::new(stats.shield_capacity, stats.shield_regen, rules.shields),
+            Hull::new(stats.hull),
+            Thruster {
+                thruster_time: 0.,
+                thrusting: false,
+                fuel: 1.,
+                thrust: stats.thrust,
+            },
+            Gun {
+                time: 0.,
+                cooldown: stats.gun_cooldown,
+                shot_speed: stats.shot_speed,
+                shot_lifetime: stats.shot_lifetime,
+                barrels: stats.barrels,
+                muzzle: stats.radius + SHOT_RADIUS + 2.,
+            },
+            class.flight(rules.flight_model),
+            Controls::default(),
+            Reactor::default(),
+            Cargo::default(),
+            Trajectory {
+                visible: false,
+                seconds: 3.,
+            },
+        ))
+        .with_children(|p| {
+            let arcs = match rules.shields {
+                ShieldLayout::Bubble => vec![mesh_handles.shield.clone()],
+                ShieldLayout::FrontRear => vec![
+                    mesh_handles.shield_front.clone(),
+                    mesh_handles.shield_rear.clone(),
+                ],
+            };
+            for (arc, mesh) in arcs.into_iter().enumerate() {
+                p.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: mesh.into(),
+                        // the shield meshes have a radius of 16
+                        transform: Transform::from_scale(Vec3::splat(stats.radius / 16.)),
+                        material: materials.add(ColorMaterial::from(Color::WHITE)),
+                        ..Default::default()
+                    },
+                    ShieldRing {
+                        arc,
+                        energy: 1.0,
+                        flash: 0.,
+                        color: team_color(spawn.team()),
+                    },
+                ));
+            }
+        })
+        .id()
 }
 
 fn spawn_asteroid(
@@ -294,6 +677,7 @@ fn spawn_asteroid(
     mesh_handles: &MeshHandles,
     size: f32,
     velocity: Velocity,
+    ore: Ore,
 ) {
     let vertices: Vec<Vec2> = create_asteroid().iter().map(|v| v.xy() * 8.).collect();
 
@@ -303,7 +687,7 @@ fn spawn_asteroid(
             transform: Transform::default()
                 .with_translation(pos)
                 .with_scale(Vec3::splat(size)),
-            material: mesh_handles.material.clone(),
+            material: mesh_handles.ore_materials[ore.index()].clone(),
             ..Default::default()
         },
         Celestial {
@@ -314,108 +698,127 @@ fn spawn_asteroid(
             ..Default::default()
         },
         Collider::convex_hull(vertices.as_slice()).unwrap(),
-        Shield { energy: 0.1 },
-        Asteroid,
+        Hull::new(0.1),
+        CollisionCategory::Asteroid,
+        Asteroid { ore },
     ));
 }
 
-fn init_energy_display(
-    ships: Query<(Entity, &Transform), Changed<Ship>>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+fn load_shield(
+    mut ships: Query<(&mut Shield, &Reactor), With<Ship>>,
+    timer: Res<Time>,
+    level: Res<Level>,
 ) {
-    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
-
-    let text_style = TextStyle {
-        font: font.clone(),
-        font_size: 10.0,
-        color: Color::WHITE,
-    };
-    for (ship, &transform) in &ships {
-        commands.spawn((
-            Text2dBundle {
-                text: Text::from_section("asdsd", text_style.clone()),
-                transform,
-                ..default()
-            },
-            EnergyDisplay { ship },
-        ));
-    }
-}
-
-fn load_shield(mut ships: Query<&mut Shield, With<Ship>>, timer: Res<Time>) {
-    for mut ship in &mut ships {
-        if ship.energy >= 0. {
-            ship.energy += timer.delta_seconds() * HEAL_SPEED;
-            if ship.energy > 1. {
-                ship.energy = 1.;
-            }
+    for (mut shield, reactor) in &mut ships {
+        let charge = timer.delta_seconds()
+            * level.rules.heal_speed
+            * shield.regen
+            * reactor.factor(PowerSystem::Shields);
+        let capacity = shield.capacity;
+        for energy in &mut shield.arcs {
+            *energy = (*energy + charge).min(capacity);
         }
     }
 }
 
-fn arrange_energy_display(
-    ships: Query<(&Transform, &Shield), With<Ship>>,
-    mut displays: Query<(Entity, &mut Transform, &EnergyDisplay, &mut Text), Without<Ship>>,
-    mut commands: Commands,
+// ships close to a moon's surface refuel quickly, everywhere else the tank refills slowly
+const REFUEL_RANGE: f32 = 60.;
+
+fn load_fuel(
+    mut ships: Query<(&mut Thruster, &Transform), With<Ship>>,
+    moons: Query<&Transform, With<Moon>>,
+    timer: Res<Time>,
+    level: Res<Level>,
 ) {
-    for (display_entity, mut transform, display, mut text) in &mut displays {
-        if let Ok((ship_transform, shield)) = ships.get(display.ship) {
-            if shield.energy < 0. {
-                commands.entity(display_entity).despawn();
-            }
-            transform.translation = ship_transform.translation + Vec3::new(-20., 30., 0.);
-            text.sections = vec![TextSection::from(format!(
-                "{:0} %",
-                (100. * shield.energy) as i32
-            ))];
+    for (mut thruster, transform) in &mut ships {
+        let near_moon = moons.iter().any(|moon| {
+            moon.translation.xy().distance(transform.translation.xy()) < moon.scale.x + REFUEL_RANGE
+        });
+        let speed = if near_moon {
+            level.rules.refuel_speed
+        } else {
+            level.rules.fuel_regen
+        };
+        if !thruster.thrusting {
+            thruster.fuel = (thruster.fuel + timer.delta_seconds() * speed).min(1.);
         }
+        thruster.fuel = thruster.fuel.max(0.);
     }
 }
 
+const SHIELD_FLASH_DECAY: f32 = 4.;
+const SHIELD_PULSE_SPEED: f32 = 8.;
+
 fn copy_shield_value(
-    ship_shield: Query<&Shield, (With<Ship>, Changed<Shield>)>,
-    mut shield: Query<(&mut Shield, &Parent, &mut Handle<ColorMaterial>), Without<Ship>>,
+    ships: Query<&Shield, With<Ship>>,
+    mut rings: Query<(&mut ShieldRing, &Parent, &Handle<ColorMaterial>)>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<Time>,
 ) {
-    for (mut shield, parent, mut material) in &mut shield {
-        if let Ok(p) = ship_shield.get(parent.get()) {
-            shield.energy = p.energy;
-            let x = materials.add(ColorMaterial::from(Color::rgba(1., 1., 1., p.energy)));
-            *material = x;
+    for (mut ring, parent, material) in &mut rings {
+        let Ok(shield) = ships.get(parent.get()) else {
+            continue;
+        };
+        let energy = shield.arc_fraction(ring.arc);
+        if energy < ring.energy {
+            ring.flash = 1.;
+        } else {
+            ring.flash = (ring.flash - time.delta_seconds() * SHIELD_FLASH_DECAY).max(0.);
+        }
+        let regenerating = energy > ring.energy && energy < 1.;
+        ring.energy = energy;
+
+        let pulse = if regenerating {
+            0.15 * (1. + (time.elapsed_seconds() * SHIELD_PULSE_SPEED).sin())
+        } else {
+            0.
+        };
+        let alpha = (ring.energy + pulse).clamp(0., 1.);
+        let fade = 1. - ring.flash;
+        let color = Color::rgba(
+            ring.color.r() * fade + ring.flash,
+            ring.color.g() * fade,
+            ring.color.b() * fade,
+            alpha + ring.flash * (1. - alpha),
+        );
+        if materials.get(material).is_some_and(|m| m.color != color) {
+            if let Some(m) = materials.get_mut(material) {
+                m.color = color;
+            }
         }
     }
 }
 
-const GRAVITY_SCALE: f32 = 0.3;
-
 fn apply_gravity(
     all_masses: Query<(Entity, &Transform, &ReadMassProperties)>,
     mut forced_masses: Query<(Entity, &Transform, &ReadMassProperties, &mut ExternalForce)>,
+    level: Res<Level>,
 ) {
     for (forced_entity, forced_pos, forced_mass, mut force) in &mut forced_masses {
-        let mut force_sum = Vec3::ZERO;
+        let mut force_sum = Vec2::ZERO;
         for (other_entity, other_pos, other_mass) in &all_masses {
             if forced_entity != other_entity {
-                let direction = other_pos.translation - forced_pos.translation;
-                force_sum += GRAVITY_SCALE * direction * (forced_mass.mass * other_mass.mass)
-                    / direction.length_squared();
+                let direction = (other_pos.translation - forced_pos.translation).xy();
+                force_sum += gravity_force(
+                    level.rules.gravity_scale,
+                    direction,
+                    forced_mass.mass,
+                    other_mass.mass,
+                );
             }
         }
-        force.force = force_sum.xy();
+        force.force = force_sum;
     }
 }
 
 fn kill(
     mut reader: EventReader<Boom>,
-    asteroids: Query<(&Transform, &Velocity), With<Asteroid>>,
-    positions: Query<(&Transform, &Velocity)>,
+    asteroids: Query<(&Transform, &Velocity, &Asteroid)>,
     mut commands: Commands,
     mesh_handles: Res<MeshHandles>,
-    time: Res<Time>,
 ) {
     for event in reader.read() {
-        if let Ok((asteroid_transform, velocity)) = asteroids.get(event.entity) {
+        if let Ok((asteroid_transform, velocity, asteroid)) = asteroids.get(event.entity) {
             if asteroid_transform.scale.x > 1. {
                 for _ in 0..4 {
                     spawn_asteroid(
@@ -429,43 +832,85 @@ fn kill(
                         &mesh_handles,
                         asteroid_transform.scale.x / 2.,
                         velocity.clone(),
+                        asteroid.ore,
                     );
                 }
             }
         }
-        if let Some(mut e) = commands.get_entity(event.entity) {
-            e.despawn();
-        }
-        if let Ok((pos, speed)) = positions.get(event.entity) {
-            for _ in 0..4 {
-                let v = Vec2::from_angle(rand::random::<f32>() * 3.1415 * 2.);
-                spawn_debris(
-                    &mut commands,
-                    &mesh_handles,
-                    pos.translation,
-                    speed.linvel + v * DEBRIS_SPEED,
-                    &time,
-                );
-            }
+        if let Some(e) = commands.get_entity(event.entity) {
+            e.despawn_recursive();
         }
     }
 }
 
+// who is on which side and who gets the credit for a kill
+#[derive(SystemParam)]
+struct Allegiance<'w, 's> {
+    owners: Query<'w, 's, &'static Owner>,
+    ships: Query<'w, 's, &'static Ship>,
+    teams: Query<'w, 's, &'static Team>,
+}
+
+#[derive(SystemParam)]
+struct DamageEvents<'w> {
+    booms: EventWriter<'w, Boom>,
+    hits: EventWriter<'w, Hit>,
+}
+
 fn check_collisions(
     mut reader2: EventReader<ContactForceEvent>,
-    mut objects: Query<&mut Shield>,
+    mut objects: Query<(&mut Hull, Option<&mut Shield>, &Transform)>,
     asteroids: Query<&Asteroid>,
-    mut writer: EventWriter<'_, Boom>,
+    allegiance: Allegiance,
+    mut events: DamageEvents,
+    level: Res<Level>,
 ) {
+    let Allegiance {
+        owners,
+        ships,
+        teams,
+    } = allegiance;
     for event in reader2.read() {
         if asteroids.get(event.collider1).is_ok() && asteroids.get(event.collider2).is_ok() {
             trace!("Both asteroids!");
         } else {
-            for entity in vec![event.collider1, event.collider2] {
-                if let Ok(mut ship) = objects.get_mut(entity) {
-                    (*ship).energy -= 0.2;
-                    if ship.energy < 0. {
-                        writer.send(Boom { entity });
+            // the contact normal points from the first collider towards the second
+            for (entity, other, towards) in [
+                (event.collider1, event.collider2, event.max_force_direction),
+                (event.collider2, event.collider1, -event.max_force_direction),
+            ] {
+                let same_team = matches!(
+                    (teams.get(entity), teams.get(other)),
+                    (Ok(a), Ok(b)) if a == b
+                );
+                let damage = if same_team {
+                    level.rules.collision_damage * friendly_fire_factor(level.rules.friendly_fire)
+                } else {
+                    level.rules.collision_damage
+                };
+                if damage <= 0. {
+                    continue;
+                }
+                if let Ok((mut hull, shield, transform)) = objects.get_mut(entity) {
+                    // the shield arc facing the contact takes the damage first
+                    let through = match shield {
+                        Some(mut shield) => {
+                            let local = (transform.rotation.inverse() * towards.extend(0.)).xy();
+                            let arc = shield.arc_towards(local);
+                            shield.absorb(arc, damage)
+                        }
+                        None => damage,
+                    };
+                    let was_alive = hull.integrity >= 0.;
+                    hull.integrity -= through;
+                    events.hits.send(Hit { entity, damage });
+                    if was_alive && hull.integrity < 0. {
+                        let by = owners
+                            .get(other)
+                            .map(|owner| owner.player)
+                            .or_else(|_| ships.get(other).map(|ship| ship.player))
+                            .ok();
+                        events.booms.send(Boom { entity, by });
                     }
                 }
             }
@@ -473,24 +918,22 @@ fn check_collisions(
     }
 }
 
-fn warp_space(mut query: Query<&mut Transform>) {
+// only bodies wrap, cameras and ui nodes keep their transforms
+fn warp_space(mut query: Query<&mut Transform, With<RigidBody>>, level: Res<Level>) {
     for mut transform in &mut query {
-        if transform.translation.x < -SPACE_SIZE {
-            transform.translation.x += 2. * SPACE_SIZE;
-        }
-        if transform.translation.y < -SPACE_SIZE {
-            transform.translation.y += 2. * SPACE_SIZE;
-        }
-        if transform.translation.x > SPACE_SIZE {
-            transform.translation.x -= 2. * SPACE_SIZE;
-        }
-        if transform.translation.y > SPACE_SIZE {
-            transform.translation.y -= 2. * SPACE_SIZE;
+        let position = transform.translation.xy();
+        let wrapped = level.wrap(position);
+        if wrapped != position {
+            transform.translation = wrapped.extend(transform.translation.z);
         }
     }
 }
 
-fn kill_debris(query: Query<(Entity, &Lifetime)>, time: Res<Time>, mut commands: Commands) {
+fn kill_debris(
+    query: Query<(Entity, &Lifetime), Without<Pooled>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
     for (entity, lifetime) in &query {
         if lifetime.death < time.elapsed_seconds() {
             commands.entity(entity).despawn();
@@ -498,14 +941,9 @@ fn kill_debris(query: Query<(Entity, &Lifetime)>, time: Res<Time>, mut commands:
     }
 }
 
-const THRUSTER_TIME: f32 = 0.05;
-const THRUSTER_LIFETIME: f32 = 0.5;
-const THRUSTER_SPEED: f32 = 200.;
-const DEBRIS_SPEED: f32 = 50.;
-const GUN_TIME: f32 = 0.2;
-const GUN_LIFETIME: f32 = 1.0;
-const SHOT_SPEED: f32 = 400.;
-const SPACE_SIZE: f32 = 400.;
+// sideways distance between the barrels of a multi-barrel gun
+const BARREL_SPACING: f32 = 8.;
+const SHOT_RADIUS: f32 = 2.;
 
 #[derive(Clone)]
 struct KeyConfig {
@@ -514,6 +952,9 @@ struct KeyConfig {
     right: KeyCode,
     shoot: KeyCode,
     teleport: KeyCode,
+    trajectory: KeyCode,
+    // routes power to the shields, weapons and engines
+    power: [KeyCode; 3],
     player: u8,
 }
 
@@ -526,6 +967,8 @@ fn steering_config() -> Vec<KeyConfig> {
             right: KeyCode::ArrowRight,
             shoot: KeyCode::Space,
             teleport: KeyCode::KeyT,
+            trajectory: KeyCode::ShiftRight,
+            power: [KeyCode::Delete, KeyCode::End, KeyCode::PageDown],
         },
         KeyConfig {
             player: 1,
@@ -534,10 +977,67 @@ fn steering_config() -> Vec<KeyConfig> {
             right: KeyCode::KeyD,
             shoot: KeyCode::KeyS,
             teleport: KeyCode::Escape,
+            trajectory: KeyCode::KeyE,
+            power: [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3],
+        },
+        KeyConfig {
+            player: 2,
+            thrust: KeyCode::Numpad8,
+            left: KeyCode::Numpad4,
+            right: KeyCode::Numpad6,
+            shoot: KeyCode::Numpad5,
+            teleport: KeyCode::NumpadDecimal,
+            trajectory: KeyCode::Numpad0,
+            power: [
+                KeyCode::NumpadDivide,
+                KeyCode::NumpadMultiply,
+                KeyCode::NumpadSubtract,
+            ],
+        },
+        KeyConfig {
+            player: 3,
+            thrust: KeyCode::KeyY,
+            left: KeyCode::KeyG,
+            right: KeyCode::KeyJ,
+            shoot: KeyCode::KeyH,
+            teleport: KeyCode::KeyB,
+            trajectory: KeyCode::KeyU,
+            power: [KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8],
         },
     ]
 }
 
+// gamepads are handed out to the players in the order they were connected, a pad that is
+// unplugged leaves its slot free for the next one so the other players keep theirs
+#[derive(Resource, Default)]
+struct GamepadSlots(Vec<Option<Gamepad>>);
+
+fn track_gamepads(
+    mut connections: EventReader<GamepadConnectionEvent>,
+    mut slots: ResMut<GamepadSlots>,
+) {
+    for event in connections.read() {
+        let gamepad = Some(event.gamepad);
+        if event.connected() {
+            if slots.0.contains(&gamepad) {
+                continue;
+            }
+            match slots.0.iter_mut().find(|slot| slot.is_none()) {
+                Some(slot) => *slot = gamepad,
+                None => slots.0.push(gamepad),
+            }
+        } else if let Some(slot) = slots.0.iter_mut().find(|slot| **slot == gamepad) {
+            *slot = None;
+        }
+    }
+}
+
+fn gamepad_for(slots: &GamepadSlots, player: u8) -> Option<Gamepad> {
+    slots.0.get(player as usize).copied().flatten()
+}
+
+const STICK_DEADZONE: f32 = 0.25;
+
 fn get_key_config_for(player: u8) -> Option<KeyConfig> {
     steering_config()
         .iter()
@@ -546,114 +1046,189 @@ fn get_key_config_for(player: u8) -> Option<KeyConfig> {
         .into()
 }
 
-fn input_handler(
+// a player can fly with their keys and their gamepad at the same time
+fn player_controls(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(
-        &mut Velocity,
-        &mut ExternalImpulse,
-        &mut Transform,
-        &mut Thruster,
-        &mut Gun,
-        &Ship,
-    )>,
-    time: Res<Time>,
+    gamepads: Res<GamepadSlots>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut ships: Query<(&Ship, &mut Controls, &mut Reactor), Without<Npc>>,
     mut app_exit_events: ResMut<Events<bevy::app::AppExit>>,
-    mut commands: Commands,
-    mesh_handles: Res<MeshHandles>,
 ) {
-    for (mut speed, mut impulse, transform, mut thruster, mut gun, ship) in &mut query {
+    for (ship, mut controls, mut reactor) in &mut ships {
         if let Some(keys) = get_key_config_for(ship.player) {
-            gun.time -= time.delta_seconds();
-            if keyboard_input.pressed(keys.shoot) {
-                let r = transform.rotation.to_euler(EulerRot::XYZ);
-
-                let rnd = rand::random::<f32>() * 0.1 - 0.05;
-                let v = Vec2::from_angle(rnd + r.2 + 3.1415 / 2.);
-
-                if gun.time < 0. {
-                    gun.time = GUN_TIME;
-
-                    commands.spawn((
-                        MaterialMesh2dBundle {
-                            mesh: mesh_handles.shot.clone().into(),
-                            transform: Transform::default()
-                                .with_rotation(transform.rotation)
-                                .with_translation(transform.translation + v.extend(0.)),
-                            material: mesh_handles.shot_material.clone(),
-                            ..Default::default()
-                        },
-                        Debris {},
-                        RigidBody::Dynamic,
-                        GravityScale(0.0),
-                        Collider::ball(2.0),
-                        Velocity {
-                            linvel: speed.linvel + v * SHOT_SPEED,
-                            angvel: 0.,
-                        },
-                        Lifetime {
-                            death: time.elapsed_seconds() + GUN_LIFETIME,
-                        },
-                    ));
+            for (system, key) in PowerSystem::ALL.into_iter().zip(keys.power) {
+                if keyboard_input.just_pressed(key) {
+                    reactor.route(system);
+                }
+            }
+            controls.shoot = keyboard_input.pressed(keys.shoot);
+            controls.thrust = keyboard_input.pressed(keys.thrust);
+            controls.turn = if keyboard_input.pressed(keys.left) {
+                1.
+            } else if keyboard_input.pressed(keys.right) {
+                -1.
+            } else {
+                0.
+            };
+        }
+        if let Some(gamepad) = gamepad_for(&gamepads, ship.player) {
+            let button =
+                |button_type| gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type));
+            controls.shoot |= button(GamepadButtonType::South);
+            controls.thrust |= button(GamepadButtonType::RightTrigger2);
+            for (system, button_type) in PowerSystem::ALL.into_iter().zip([
+                GamepadButtonType::West,
+                GamepadButtonType::North,
+                GamepadButtonType::East,
+            ]) {
+                if gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type)) {
+                    reactor.route(system);
                 }
             }
-            if keyboard_input.pressed(keys.thrust) {
-                let r = transform.rotation.to_euler(EulerRot::XYZ);
+            let stick = gamepad_axes
+                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+                .unwrap_or(0.);
+            if stick.abs() > STICK_DEADZONE {
+                controls.turn = -stick;
+            } else if button(GamepadButtonType::DPadLeft) {
+                controls.turn = 1.;
+            } else if button(GamepadButtonType::DPadRight) {
+                controls.turn = -1.;
+            }
+        }
+    }
+    if keyboard_input.pressed(KeyCode::KeyQ) {
+        app_exit_events.send(bevy::app::AppExit);
+    }
+}
+
+// everything `input_handler` needs to turn a ship's controls into flight and shots
+type PilotedShip<'a> = (
+    &'a mut Velocity,
+    &'a mut ExternalImpulse,
+    &'a mut Transform,
+    &'a mut Thruster,
+    &'a mut Gun,
+    &'a Ship,
+    &'a Flight,
+    &'a ReadMassProperties,
+    &'a Controls,
+    &'a Team,
+    &'a Reactor,
+);
+
+fn input_handler(
+    mut query: Query<PilotedShip>,
+    time: Res<Time>,
+    level: Res<Level>,
+    mut commands: Commands,
+    mesh_handles: Res<MeshHandles>,
+    mut shot_pool: ResMut<EntityPool<Shot>>,
+    mut shots_fired: EventWriter<ShotFired>,
+) {
+    for (
+        mut speed,
+        mut impulse,
+        transform,
+        mut thruster,
+        mut gun,
+        ship,
+        flight,
+        mass,
+        controls,
+        team,
+        reactor,
+    ) in &mut query
+    {
+        gun.time -= time.delta_seconds() * reactor.factor(PowerSystem::Weapons);
+        if controls.shoot {
+            let r = transform.rotation.to_euler(EulerRot::XYZ);
 
-                let rnd = rand::random::<f32>() * 0.3 - 0.15;
-                let v = Vec2::from_angle(rnd + r.2 + 3.1415 / 2.);
-                impulse.impulse = v * 100000. * time.delta_seconds();
+            let rnd = rand::random::<f32>() * 0.1 - 0.05;
+            let v = Vec2::from_angle(rnd + r.2 + 3.1415 / 2.);
 
-                // speed_up
-                thruster.thruster_time += time.delta_seconds();
-                if thruster.thruster_time > THRUSTER_TIME {
-                    thruster.thruster_time -= THRUSTER_TIME;
+            if gun.time < 0. {
+                gun.time = gun.cooldown;
 
-                    spawn_debris(
+                for barrel in 0..gun.barrels {
+                    let offset =
+                        (barrel as f32 - (gun.barrels - 1) as f32 / 2.) * BARREL_SPACING * v.perp();
+                    spawn_shot(
                         &mut commands,
+                        &mut shot_pool,
                         &mesh_handles,
-                        transform.translation,
-                        speed.linvel - v * THRUSTER_SPEED,
-                        &time,
+                        Transform::default()
+                            .with_rotation(transform.rotation)
+                            .with_translation(
+                                transform.translation + (v * gun.muzzle + offset).extend(0.),
+                            ),
+                        speed.linvel + v * gun.shot_speed,
+                        ship.player,
+                        *team,
+                        time.elapsed_seconds() + gun.shot_lifetime,
                     );
                 }
-            }
-            if keyboard_input.pressed(keys.left) {
-                // instead - maybe add a force?
-                speed.angvel = 5.;
-            } else if keyboard_input.pressed(keys.right) {
-                speed.angvel = -5.;
-            } else {
-                speed.angvel = 0.;
+                shots_fired.send(ShotFired);
             }
         }
-    }
-    if keyboard_input.pressed(KeyCode::KeyQ) {
-        app_exit_events.send(bevy::app::AppExit);
+        thruster.thrusting = controls.thrust && thruster.fuel > 0.;
+        if thruster.thrusting {
+            thruster.fuel -= time.delta_seconds() * level.rules.fuel_burn;
+
+            let r = transform.rotation.to_euler(EulerRot::XYZ);
+
+            let rnd = rand::random::<f32>() * 0.3 - 0.15;
+            let v = Vec2::from_angle(rnd + r.2 + 3.1415 / 2.);
+            impulse.impulse =
+                v * thruster.thrust * reactor.factor(PowerSystem::Engines) * time.delta_seconds();
+        }
+        steer(
+            flight,
+            controls.turn,
+            &mut speed,
+            &mut impulse,
+            mass,
+            time.delta_seconds(),
+        );
     }
 }
 
-fn spawn_debris(
+#[allow(clippy::too_many_arguments)]
+fn spawn_shot(
     commands: &mut Commands,
-    mesh_handles: &Res<MeshHandles>,
-    pos: Vec3,
-    speed: Vec2,
-    time: &Res<Time>,
+    pool: &mut EntityPool<Shot>,
+    mesh_handles: &MeshHandles,
+    transform: Transform,
+    linvel: Vec2,
+    player: u8,
+    team: Team,
+    death: f32,
 ) {
-    commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: mesh_handles.debris.clone().into(),
-            transform: Transform::default().with_translation(pos),
-            material: mesh_handles.debris_material.clone(),
-            ..Default::default()
-        },
-        Debris {},
-        RigidBody::Dynamic,
-        Velocity {
-            linvel: speed,
-            angvel: 0.,
-        },
-        Lifetime {
-            death: time.elapsed_seconds() + THRUSTER_LIFETIME,
-        },
-    ));
+    let state = (
+        transform,
+        Velocity { linvel, angvel: 0. },
+        Owner { player },
+        team,
+        Lifetime { death },
+    );
+    if let Some(entity) = pool.take(commands) {
+        commands.entity(entity).insert(state);
+    } else {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh_handles.shot.clone().into(),
+                material: mesh_handles.shot_material.clone(),
+                ..Default::default()
+            },
+            Debris {},
+            Shot,
+            Pooled,
+            RigidBody::Dynamic,
+            GravityScale(0.0),
+            Collider::ball(SHOT_RADIUS),
+            CollisionCategory::Projectile,
+            state,
+        ));
+    }
 }
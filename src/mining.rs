@@ -0,0 +1,254 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy_rapier2d::prelude::*;
+
+use crate::collisions::CollisionCategory;
+use crate::components::*;
+use crate::players::*;
+use crate::{Boom, Celestial, MeshHandles};
+
+const ORE_RADIUS: f32 = 6.;
+const ORE_LIFETIME: f32 = 20.;
+// how fast the chunks fly apart from the wreck
+const ORE_SCATTER: f32 = 40.;
+const CARGO_CAPACITY: u32 = 10;
+const DOCK_RANGE: f32 = 60.;
+// the first level costs this much ore, every further level this much more
+const UPGRADE_COST: u32 = 3;
+const MAX_UPGRADE: u32 = 3;
+
+// iron plates the shield, ice feeds the engine and crystal tunes the gun
+#[derive(Clone, Copy, PartialEq)]
+pub enum Upgrade {
+    Shield,
+    Engine,
+    Gun,
+}
+
+impl Upgrade {
+    pub fn bought_with(self) -> Ore {
+        match self {
+            Upgrade::Shield => Ore::Iron,
+            Upgrade::Engine => Ore::Ice,
+            Upgrade::Gun => Ore::Crystal,
+        }
+    }
+}
+
+pub fn ore_color(ore: Ore) -> Color {
+    match ore {
+        Ore::Iron => Color::rgb(0.75, 0.5, 0.35),
+        Ore::Ice => Color::rgb(0.6, 0.85, 1.),
+        Ore::Crystal => Color::rgb(0.85, 0.4, 1.),
+    }
+}
+
+fn ore_name(ore: Ore) -> &'static str {
+    match ore {
+        Ore::Iron => "iron",
+        Ore::Ice => "ice",
+        Ore::Crystal => "crystal",
+    }
+}
+
+// iron is common, crystal is rare
+pub fn random_ore() -> Ore {
+    match rand::random::<f32>() {
+        roll if roll < 0.6 => Ore::Iron,
+        roll if roll < 0.9 => Ore::Ice,
+        _ => Ore::Crystal,
+    }
+}
+
+pub fn upgrade_level(delivered: u32) -> u32 {
+    let mut level = 0;
+    let mut left = delivered;
+    while level < MAX_UPGRADE && left >= UPGRADE_COST * (level + 1) {
+        left -= UPGRADE_COST * (level + 1);
+        level += 1;
+    }
+    level
+}
+
+fn spawn_ore(
+    commands: &mut Commands,
+    mesh_handles: &MeshHandles,
+    ore: Ore,
+    position: Vec2,
+    velocity: Vec2,
+    now: f32,
+) {
+    let direction = Vec2::from_angle(rand::random::<f32>() * TAU);
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: mesh_handles.ore.clone().into(),
+            transform: Transform::from_translation((position + direction * 8.).extend(0.)),
+            material: mesh_handles.ore_materials[ore.index()].clone(),
+            ..Default::default()
+        },
+        Celestial {
+            events: ActiveEvents::COLLISION_EVENTS,
+            velocity: Velocity {
+                linvel: velocity + direction * ORE_SCATTER * rand::random::<f32>(),
+                angvel: rand::random::<f32>() * 4. - 2.,
+            },
+            ..Celestial::default()
+        },
+        Collider::ball(ORE_RADIUS),
+        Sensor,
+        ColliderMassProperties::Density(0.2),
+        CollisionCategory::Pickup,
+        OrePickup { ore },
+        Lifetime {
+            death: now + ORE_LIFETIME,
+        },
+    ));
+}
+
+// runs between the damage of the frame and `kill`,
+// asteroids give ore by size and a wrecked ship spills its cargo
+pub fn drop_ore(
+    mut reader: EventReader<Boom>,
+    asteroids: Query<(&Transform, &Velocity, &Asteroid)>,
+    ships: Query<(&Transform, &Velocity, &Cargo)>,
+    mut commands: Commands,
+    mesh_handles: Res<MeshHandles>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for event in reader.read() {
+        if let Ok((transform, velocity, asteroid)) = asteroids.get(event.entity) {
+            for _ in 0..transform.scale.x.ceil() as u32 {
+                spawn_ore(
+                    &mut commands,
+                    &mesh_handles,
+                    asteroid.ore,
+                    transform.translation.xy(),
+                    velocity.linvel,
+                    now,
+                );
+            }
+        }
+        if let Ok((transform, velocity, cargo)) = ships.get(event.entity) {
+            for ore in Ore::ALL {
+                for _ in 0..cargo.ore[ore.index()] {
+                    spawn_ore(
+                        &mut commands,
+                        &mesh_handles,
+                        ore,
+                        transform.translation.xy(),
+                        velocity.linvel,
+                        now,
+                    );
+                }
+            }
+        }
+    }
+}
+
+pub fn collect_ore(
+    mut collisions: EventReader<CollisionEvent>,
+    pickups: Query<&OrePickup>,
+    mut ships: Query<&mut Cargo, With<Ship>>,
+    mut commands: Commands,
+) {
+    let mut collected = Vec::new();
+    for event in collisions.read() {
+        let CollisionEvent::Started(a, b, _) = event else {
+            continue;
+        };
+        for (pickup_entity, ship_entity) in [(*a, *b), (*b, *a)] {
+            let (Ok(pickup), Ok(mut cargo)) =
+                (pickups.get(pickup_entity), ships.get_mut(ship_entity))
+            else {
+                continue;
+            };
+            // a full hold leaves the ore floating for someone else
+            if collected.contains(&pickup_entity) || cargo.total() >= CARGO_CAPACITY {
+                continue;
+            }
+            cargo.ore[pickup.ore.index()] += 1;
+            collected.push(pickup_entity);
+            commands.entity(pickup_entity).despawn();
+        }
+    }
+}
+
+// flying close to a moon unloads the hold into the player's upgrades
+pub fn deliver_ore(
+    mut ships: Query<(&Ship, &Transform, &mut Cargo)>,
+    moons: Query<&Transform, With<Moon>>,
+    mut players: ResMut<Players>,
+) {
+    for (ship, transform, mut cargo) in &mut ships {
+        if cargo.total() == 0 {
+            continue;
+        }
+        let docked = moons.iter().any(|moon| {
+            moon.translation.xy().distance(transform.translation.xy()) < moon.scale.x + DOCK_RANGE
+        });
+        if !docked {
+            continue;
+        }
+        let Some(state) = players.get_mut(ship.player) else {
+            continue;
+        };
+        for (delivered, carried) in state.delivered.iter_mut().zip(&mut cargo.ore) {
+            *delivered += std::mem::take(carried);
+        }
+    }
+}
+
+// the class stats are the base, every upgrade level adds on top of them
+pub fn apply_upgrades(
+    players: Res<Players>,
+    new_ships: Query<(), Added<Ship>>,
+    mut ships: Query<(&Ship, &mut Shield, &mut Gun, &mut Thruster), Without<Ghost>>,
+) {
+    if !players.is_changed() && new_ships.is_empty() {
+        return;
+    }
+    for (ship, mut shield, mut gun, mut thruster) in &mut ships {
+        let Some(state) = players.get(ship.player) else {
+            continue;
+        };
+        let stats = state.class.stats();
+        let level = |upgrade: Upgrade| state.upgrade(upgrade) as f32;
+        shield.capacity = stats.shield_capacity * (1. + 0.25 * level(Upgrade::Shield));
+        thruster.thrust = stats.thrust * (1. + 0.15 * level(Upgrade::Engine));
+        gun.cooldown = stats.gun_cooldown * (1. - 0.15 * level(Upgrade::Gun));
+    }
+}
+
+pub fn update_cargo_text(
+    ships: Query<(&Ship, &Cargo)>,
+    players: Res<Players>,
+    mut texts: Query<(&CargoText, &mut Text)>,
+) {
+    for (cargo_text, mut text) in &mut texts {
+        let Some(state) = players.get(cargo_text.player) else {
+            continue;
+        };
+        let carried = ships
+            .iter()
+            .find(|(ship, _)| ship.player == cargo_text.player)
+            .map_or([0; 3], |(_, cargo)| cargo.ore);
+        let hold = Ore::ALL
+            .iter()
+            .map(|ore| format!("{} {}", ore_name(*ore), carried[ore.index()]))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let value = format!(
+            "{}  shield {} engine {} gun {}",
+            hold,
+            state.upgrade(Upgrade::Shield),
+            state.upgrade(Upgrade::Engine),
+            state.upgrade(Upgrade::Gun)
+        );
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+
+pub const PIXELS_PER_METER: f32 = 100.;
+
+const ORBIT_PREDICTION_TIME: f32 = 20.;
+const ORBIT_PREDICTION_STEPS: usize = 400;
+
+#[derive(Resource, Default)]
+pub struct ShowOrbits(pub bool);
+
+// forces go to rapier unscaled while positions are in pixels, see apply_gravity
+pub fn gravity_force(gravity_scale: f32, direction: Vec2, mass: f32, other_mass: f32) -> Vec2 {
+    gravity_scale * direction * (mass * other_mass) / direction.length_squared()
+}
+
+pub fn gravity_acceleration(gravity_scale: f32, direction: Vec2, other_mass: f32) -> Vec2 {
+    PIXELS_PER_METER * gravity_force(gravity_scale, direction, 1., other_mass)
+}
+
+// stars and black holes pull on the moon too, but only moons are ever launched
+pub fn launch_circular_orbits(
+    launching: Query<Entity, With<CircularOrbit>>,
+    mut moons: Query<(Entity, &Transform, &ReadMassProperties, &mut Velocity), Gravitating>,
+    mut commands: Commands,
+    level: Res<Level>,
+) {
+    if launching.is_empty() || moons.iter().any(|(_, _, mass, _)| mass.mass <= 0.) {
+        return;
+    }
+    let bodies: Vec<(Entity, Vec2, f32)> = moons
+        .iter()
+        .map(|(entity, transform, mass, _)| (entity, transform.translation.xy(), mass.mass))
+        .collect();
+    for entity in &launching {
+        let Ok((_, transform, own_mass, mut velocity)) = moons.get_mut(entity) else {
+            continue;
+        };
+        let own_mass = own_mass.mass;
+        let position = transform.translation.xy();
+        let others = bodies.iter().filter(|(other, _, _)| *other != entity);
+        let total_mass: f32 = others.clone().map(|(_, _, mass)| mass).sum();
+        if total_mass <= 0. {
+            warn!("a moon on a circular orbit has nothing to circle, it stays where it is");
+            commands.entity(entity).remove::<CircularOrbit>();
+            continue;
+        }
+        // both bodies circle the common barycenter, which includes the launched moon itself
+        let barycenter = (others
+            .clone()
+            .map(|(_, pos, mass)| *pos * *mass)
+            .sum::<Vec2>()
+            + position * own_mass)
+            / (total_mass + own_mass);
+        let acceleration: Vec2 = others
+            .map(|(_, pos, mass)| {
+                gravity_acceleration(level.rules.gravity_scale, *pos - position, *mass)
+            })
+            .sum();
+        let radius = barycenter - position;
+        let speed = (acceleration.length() * radius.length()).sqrt();
+        velocity.linvel = radius.normalize_or_zero().perp() * speed;
+        commands.entity(entity).remove::<CircularOrbit>();
+    }
+}
+
+pub fn follow_rails(
+    rails: Query<(Entity, &Rail)>,
+    mut transforms: Query<&mut Transform>,
+    time: Res<Time>,
+) {
+    let t = time.elapsed_seconds();
+    let targets: Vec<(Entity, Vec2)> = rails
+        .iter()
+        .map(|(entity, rail)| {
+            (
+                entity,
+                rail_center(rail, &transforms.to_readonly()) + rail.offset_at(t),
+            )
+        })
+        .collect();
+    for (entity, position) in targets {
+        if let Ok(mut transform) = transforms.get_mut(entity) {
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
+
+pub fn rail_center(rail: &Rail, transforms: &Query<&Transform>) -> Vec2 {
+    rail.around
+        .and_then(|anchor| transforms.get(anchor).ok())
+        .map(|transform| transform.translation.xy())
+        .unwrap_or(rail.center)
+}
+
+pub struct OrbitBody {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub mass: f32,
+    pub rail: Option<(Vec2, Rail)>,
+    // fixed moons and hazards attract the others but stay where they are
+    pub fixed: bool,
+}
+
+// what a query has to fetch for `OrbitBody::new`
+pub type OrbitBodyData<'a> = (
+    &'a Transform,
+    &'a Velocity,
+    &'a ReadMassProperties,
+    &'a RigidBody,
+    Option<&'a Rail>,
+);
+
+// moons and hazards are the bodies that shape the orbits
+type Gravitating = Or<(With<Moon>, With<Hazard>)>;
+
+impl OrbitBody {
+    pub fn new(
+        (transform, velocity, mass, body, rail): OrbitBodyData,
+        transforms: &Query<&Transform>,
+    ) -> Self {
+        OrbitBody {
+            position: transform.translation.xy(),
+            velocity: velocity.linvel,
+            mass: mass.mass,
+            rail: rail.map(|rail| (rail_center(rail, transforms), *rail)),
+            fixed: *body == RigidBody::Fixed,
+        }
+    }
+}
+
+// integrates the bodies under their mutual gravity, railed bodies just follow their rail
+pub fn predict_orbits(
+    bodies: &[OrbitBody],
+    level: &Level,
+    now: f32,
+    duration: f32,
+    steps: usize,
+) -> Vec<Vec<Vec2>> {
+    let dt = duration / steps as f32;
+    let mut positions: Vec<Vec2> = bodies.iter().map(|body| body.position).collect();
+    let mut velocities: Vec<Vec2> = bodies.iter().map(|body| body.velocity).collect();
+    let mut paths: Vec<Vec<Vec2>> = positions.iter().map(|p| vec![*p]).collect();
+    for step in 1..=steps {
+        let t = now + step as f32 * dt;
+        for i in 0..bodies.len() {
+            if let Some((center, rail)) = &bodies[i].rail {
+                positions[i] = *center + rail.offset_at(t);
+                continue;
+            }
+            if bodies[i].fixed {
+                continue;
+            }
+            let mut acceleration = Vec2::ZERO;
+            for (j, other) in bodies.iter().enumerate() {
+                if i != j {
+                    acceleration += gravity_acceleration(
+                        level.rules.gravity_scale,
+                        positions[j] - positions[i],
+                        other.mass,
+                    );
+                }
+            }
+            velocities[i] += acceleration * dt;
+            positions[i] = level.wrap(positions[i] + velocities[i] * dt);
+        }
+        for (path, position) in paths.iter_mut().zip(&positions) {
+            path.push(*position);
+        }
+    }
+    paths
+}
+
+pub fn toggle_orbits(keyboard_input: Res<ButtonInput<KeyCode>>, mut show: ResMut<ShowOrbits>) {
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        show.0 = !show.0;
+    }
+}
+
+pub fn draw_orbits(
+    show: Res<ShowOrbits>,
+    moons: Query<OrbitBodyData, Gravitating>,
+    transforms: Query<&Transform>,
+    level: Res<Level>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+) {
+    if !show.0 {
+        return;
+    }
+    let bodies: Vec<OrbitBody> = moons
+        .iter()
+        .map(|moon| OrbitBody::new(moon, &transforms))
+        .collect();
+    for body in &bodies {
+        if let Some((center, rail)) = &body.rail {
+            gizmos.circle_2d(*center, rail.radius, Color::DARK_GRAY);
+        }
+    }
+    let paths = predict_orbits(
+        &bodies,
+        &level,
+        time.elapsed_seconds(),
+        ORBIT_PREDICTION_TIME,
+        ORBIT_PREDICTION_STEPS,
+    );
+    for (body, path) in bodies.iter().zip(paths) {
+        if body.rail.is_none() && !body.fixed {
+            draw_wrapped_path(&mut gizmos, &path, &level, Color::DARK_GRAY);
+        }
+    }
+}
+
+// splits the path where it wraps around the arena instead of drawing a line across it
+pub fn draw_wrapped_path(gizmos: &mut Gizmos, path: &[Vec2], level: &Level, color: Color) {
+    for segment in wrapped_segments(path, level) {
+        gizmos.linestrip_2d(segment.iter().copied(), color);
+    }
+}
+
+pub fn wrapped_segments<'a>(path: &'a [Vec2], level: &Level) -> Vec<&'a [Vec2]> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for i in 1..=path.len() {
+        if i == path.len() || path[i].distance(path[i - 1]) > level.space_size {
+            segments.push(&path[start..i]);
+            start = i;
+        }
+    }
+    segments
+}
@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+use bevy::render::{
+    mesh::PrimitiveTopology, render_asset::RenderAssetUsages, view::NoFrustumCulling,
+};
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy_rapier2d::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+use crate::{create_debris, Boom};
+
+const THRUSTER_TIME: f32 = 0.05;
+const THRUSTER_LIFETIME: f32 = 0.5;
+const THRUSTER_SPEED: f32 = 200.;
+const DEBRIS_SPEED: f32 = 50.;
+const DEBRIS_LIFETIME: f32 = 0.8;
+const DEBRIS_COUNT: usize = 12;
+const PARTICLE_SIZE: f32 = 16.;
+
+pub struct Particle {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+    pub color: Color,
+}
+
+// simulated here instead of in rapier, all particles share one line mesh
+#[derive(Resource)]
+pub struct Particles {
+    pub particles: Vec<Particle>,
+    mesh: Handle<Mesh>,
+}
+
+impl Particles {
+    pub fn emit(&mut self, position: Vec2, velocity: Vec2, lifetime: f32, color: Color) {
+        self.particles.push(Particle {
+            position,
+            velocity,
+            age: 0.,
+            lifetime,
+            color,
+        });
+    }
+}
+
+pub fn setup_particles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let mesh = meshes.add(Mesh::new(
+        PrimitiveTopology::LineList,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    ));
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: mesh.clone().into(),
+            material: materials.add(ColorMaterial::from(Color::WHITE)),
+            ..Default::default()
+        },
+        // the mesh changes every frame, so its bounds from the first frame are useless
+        NoFrustumCulling,
+    ));
+    commands.insert_resource(Particles {
+        particles: Vec::new(),
+        mesh,
+    });
+}
+
+pub fn emit_exhaust(
+    mut ships: Query<(&Transform, &Velocity, &mut Thruster)>,
+    mut particles: ResMut<Particles>,
+    time: Res<Time>,
+) {
+    for (transform, velocity, mut thruster) in &mut ships {
+        if !thruster.thrusting {
+            continue;
+        }
+        thruster.thruster_time += time.delta_seconds();
+        if thruster.thruster_time > THRUSTER_TIME {
+            thruster.thruster_time -= THRUSTER_TIME;
+
+            let r = transform.rotation.to_euler(EulerRot::XYZ);
+            let rnd = rand::random::<f32>() * 0.3 - 0.15;
+            let v = Vec2::from_angle(rnd + r.2 + std::f32::consts::FRAC_PI_2);
+            particles.emit(
+                transform.translation.xy(),
+                velocity.linvel - v * THRUSTER_SPEED,
+                THRUSTER_LIFETIME,
+                Color::GRAY,
+            );
+        }
+    }
+}
+
+// needs the wreck's position, so it runs before `kill` despawns it
+pub fn emit_explosions(
+    mut reader: EventReader<Boom>,
+    positions: Query<(&Transform, &Velocity)>,
+    mut particles: ResMut<Particles>,
+) {
+    for event in reader.read() {
+        if let Ok((pos, speed)) = positions.get(event.entity) {
+            for _ in 0..DEBRIS_COUNT {
+                let v = Vec2::from_angle(rand::random::<f32>() * std::f32::consts::TAU);
+                let spread = 0.5 + rand::random::<f32>();
+                particles.emit(
+                    pos.translation.xy(),
+                    speed.linvel + v * DEBRIS_SPEED * spread,
+                    DEBRIS_LIFETIME * spread,
+                    Color::WHITE,
+                );
+            }
+        }
+    }
+}
+
+pub fn update_particles(
+    mut particles: ResMut<Particles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    particles.particles.retain_mut(|particle| {
+        particle.age += dt;
+        particle.position = level.wrap(particle.position + particle.velocity * dt);
+        particle.age < particle.lifetime
+    });
+
+    let outline: Vec<Vec2> = create_debris()
+        .iter()
+        .map(|v| v.xy() * PARTICLE_SIZE)
+        .collect();
+    let mut positions: Vec<[f32; 3]> =
+        Vec::with_capacity(particles.particles.len() * outline.len() * 2);
+    let mut colors: Vec<[f32; 4]> = Vec::with_capacity(positions.capacity());
+    for particle in &particles.particles {
+        let color = particle
+            .color
+            .with_a(particle.color.a() * (1. - particle.age / particle.lifetime))
+            .as_linear_rgba_f32();
+        for (i, a) in outline.iter().enumerate() {
+            let b = outline[(i + 1) % outline.len()];
+            positions.push((particle.position + *a).extend(0.).into());
+            positions.push((particle.position + b).extend(0.).into());
+            colors.push(color);
+            colors.push(color);
+        }
+    }
+    if positions.is_empty() {
+        // keep one invisible line, empty vertex buffers upset the renderer
+        positions = vec![[0.; 3]; 2];
+        colors = vec![[0.; 4]; 2];
+    }
+
+    if let Some(mesh) = meshes.get_mut(&particles.mesh) {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+use crate::mining::{upgrade_level, Upgrade};
+use crate::ship_class::*;
+use crate::survival::NPC_PLAYER;
+use crate::{spawn_ship, Boom, MeshHandles};
+
+const RESPAWN_TIME: f32 = 2.;
+const ASTEROID_POINTS: u32 = 1;
+const SHIP_POINTS: u32 = 5;
+
+const PLAYER_COLORS: [Color; 4] = [
+    Color::CYAN,
+    Color::ORANGE,
+    Color::LIME_GREEN,
+    Color::FUCHSIA,
+];
+
+pub fn player_color(player: u8) -> Color {
+    if player == NPC_PLAYER {
+        return Color::RED;
+    }
+    PLAYER_COLORS[player as usize % PLAYER_COLORS.len()]
+}
+
+pub struct PlayerState {
+    pub player: u8,
+    pub score: u32,
+    pub lives: u32,
+    pub respawn_at: Option<f32>,
+    pub class: ShipClass,
+    pub team: u8,
+    // ore brought to a moon, indexed by `Ore::index`
+    pub delivered: [u32; 3],
+}
+
+impl PlayerState {
+    pub fn upgrade(&self, upgrade: Upgrade) -> u32 {
+        upgrade_level(self.delivered[upgrade.bought_with().index()])
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Players {
+    pub players: Vec<PlayerState>,
+    // in survival the players draw from one pool instead of their own lives
+    pub team_lives: Option<u32>,
+}
+
+impl Players {
+    pub fn from_level(level: &Level) -> Self {
+        Players {
+            players: level
+                .spawns
+                .iter()
+                .map(|spawn| PlayerState {
+                    player: spawn.player,
+                    score: 0,
+                    lives: level.rules.lives,
+                    respawn_at: None,
+                    class: ShipClass::default(),
+                    team: spawn.team(),
+                    delivered: [0; 3],
+                })
+                .collect(),
+            team_lives: match level.rules.mode {
+                GameMode::Versus
+                | GameMode::KingOfTheHill
+                | GameMode::CaptureTheFlag
+                | GameMode::Race => None,
+                GameMode::Survival => Some(level.rules.lives * level.spawns.len() as u32),
+            },
+        }
+    }
+
+    pub fn get(&self, player: u8) -> Option<&PlayerState> {
+        self.players.iter().find(|p| p.player == player)
+    }
+
+    pub fn get_mut(&mut self, player: u8) -> Option<&mut PlayerState> {
+        self.players.iter_mut().find(|p| p.player == player)
+    }
+
+    pub fn team_score(&self, team: u8) -> u32 {
+        self.players
+            .iter()
+            .filter(|p| p.team == team)
+            .map(|p| p.score)
+            .sum()
+    }
+}
+
+// runs after the damage of the frame and before `kill` despawns the destroyed entity
+pub fn score_kills(
+    mut reader: EventReader<Boom>,
+    ships: Query<&Ship>,
+    asteroids: Query<&Asteroid>,
+    teams: Query<&Team>,
+    mut players: ResMut<Players>,
+    time: Res<Time>,
+) {
+    for event in reader.read() {
+        let points = if asteroids.contains(event.entity) {
+            ASTEROID_POINTS
+        } else if ships.contains(event.entity) {
+            SHIP_POINTS
+        } else {
+            0
+        };
+        if let Some(state) = event.by.and_then(|player| players.get_mut(player)) {
+            // shooting down a teammate earns nothing
+            let teamkill = teams
+                .get(event.entity)
+                .is_ok_and(|team| team.id == state.team);
+            if !teamkill {
+                state.score += points;
+            }
+        }
+        if let Ok(ship) = ships.get(event.entity) {
+            let Players {
+                players: states,
+                team_lives,
+            } = &mut *players;
+            if let Some(state) = states.iter_mut().find(|p| p.player == ship.player) {
+                let lives = team_lives.as_mut().unwrap_or(&mut state.lives);
+                *lives = lives.saturating_sub(1);
+                if *lives > 0 {
+                    state.respawn_at = Some(time.elapsed_seconds() + RESPAWN_TIME);
+                }
+            }
+        }
+    }
+}
+
+pub fn respawn_ships(
+    mut players: ResMut<Players>,
+    mut commands: Commands,
+    mesh_handles: Res<MeshHandles>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    for state in &mut players.players {
+        if state
+            .respawn_at
+            .is_some_and(|at| at <= time.elapsed_seconds())
+        {
+            state.respawn_at = None;
+            if let Some(spawn) = level.spawns.iter().find(|s| s.player == state.player) {
+                spawn_ship(
+                    &mut commands,
+                    &mesh_handles,
+                    &mut materials,
+                    spawn,
+                    state.class,
+                    &level.rules,
+                );
+            }
+        }
+    }
+}
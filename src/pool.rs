@@ -0,0 +1,59 @@
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::components::*;
+
+#[derive(Component)]
+pub struct Pooled;
+
+// free entities of one kind (`T` is the marker component), parked instead of despawned
+#[derive(Resource)]
+pub struct EntityPool<T> {
+    free: Vec<Entity>,
+    kind: PhantomData<T>,
+}
+
+impl<T> Default for EntityPool<T> {
+    fn default() -> Self {
+        EntityPool {
+            free: Vec::new(),
+            kind: PhantomData,
+        }
+    }
+}
+
+impl<T> EntityPool<T> {
+    // the caller inserts fresh Transform, Velocity and Lifetime
+    pub fn take(&mut self, commands: &mut Commands) -> Option<Entity> {
+        let entity = self.free.pop()?;
+        commands
+            .entity(entity)
+            .remove::<(RigidBodyDisabled, ColliderDisabled)>()
+            .insert(Visibility::Inherited);
+        Some(entity)
+    }
+
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity) {
+        commands
+            .entity(entity)
+            .insert((RigidBodyDisabled, ColliderDisabled, Visibility::Hidden))
+            .remove::<Lifetime>();
+        self.free.push(entity);
+    }
+}
+
+// every `T` is pooled, see `spawn_shot`
+pub fn recycle_expired<T: Component>(
+    query: Query<(Entity, &Lifetime), With<T>>,
+    mut pool: ResMut<EntityPool<T>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, lifetime) in &query {
+        if lifetime.death < time.elapsed_seconds() {
+            pool.release(&mut commands, entity);
+        }
+    }
+}
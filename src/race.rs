@@ -0,0 +1,430 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::collisions::CollisionCategory;
+use crate::components::*;
+use crate::level::*;
+use crate::players::*;
+use crate::ship_class::*;
+use crate::spawn_ship;
+use crate::MeshHandles;
+
+pub const GHOST_PLAYER: u8 = u8::MAX - 1;
+
+const SAVE_DIR: &str = "saves";
+// the ghost is put back on its recorded path this often, so small differences don't add up
+const KEYFRAME_INTERVAL: f32 = 0.5;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct RecordedInput {
+    pub time: f32,
+    pub thrust: bool,
+    pub turn: f32,
+    // how the reactor was split, laps saved before it was recorded flew on an even split
+    #[serde(default = "even_split")]
+    pub power: [u32; 3],
+}
+
+fn even_split() -> [u32; 3] {
+    Reactor::default().pips
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub position: Vec2,
+    pub rotation: f32,
+    pub velocity: Vec2,
+    pub angvel: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct LapRecord {
+    pub time: f32,
+    // time since the start of the lap at every checkpoint
+    pub splits: Vec<f32>,
+    pub class: String,
+    pub inputs: Vec<RecordedInput>,
+    pub keyframes: Vec<Keyframe>,
+}
+
+#[derive(Component, Default)]
+pub struct RaceProgress {
+    pub next: usize,
+    pub laps: u32,
+    pub lap_started: Option<f32>,
+    pub last_lap: Option<f32>,
+    // difference to the best lap at the last checkpoint
+    pub split_delta: Option<f32>,
+    pub finished: bool,
+    recording: LapRecord,
+}
+
+struct GhostRun {
+    entity: Entity,
+    started: f32,
+    input: usize,
+    keyframe: usize,
+}
+
+#[derive(Resource)]
+pub struct Race {
+    pub checkpoints: Vec<CheckpointSpec>,
+    pub best: Option<LapRecord>,
+    save_path: PathBuf,
+    ghost: Option<GhostRun>,
+}
+
+pub fn setup_race(mut commands: Commands, asset_server: Res<AssetServer>, level: Res<Level>) {
+    if level.rules.mode != GameMode::Race || level.checkpoints.is_empty() {
+        return;
+    }
+    let file_name = level.name.to_lowercase().replace(' ', "_") + "_best_lap.ron";
+    let save_path = PathBuf::from(SAVE_DIR).join(file_name);
+    let best = std::fs::read_to_string(&save_path)
+        .ok()
+        .and_then(|source| ron::from_str(&source).ok());
+    commands.insert_resource(Race {
+        checkpoints: level.checkpoints.clone(),
+        best,
+        save_path,
+        ghost: None,
+    });
+
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+        font_size: 14.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(8.),
+                left: Val::Px(8.),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|root| {
+            for spawn in &level.spawns {
+                root.spawn((
+                    TextBundle::from_section("", text_style.clone()),
+                    RaceText {
+                        player: spawn.player,
+                    },
+                ));
+            }
+        });
+}
+
+pub fn join_race(ships: Query<Entity, (Added<Ship>, Without<Ghost>)>, mut commands: Commands) {
+    for ship in &ships {
+        commands.entity(ship).insert(RaceProgress::default());
+    }
+}
+
+fn save_best_lap(race: &Race) {
+    let Some(best) = &race.best else {
+        return;
+    };
+    let result = std::fs::create_dir_all(SAVE_DIR)
+        .map_err(|err| err.to_string())
+        .and_then(|_| ron::ser::to_string(best).map_err(|err| err.to_string()))
+        .and_then(|source| std::fs::write(&race.save_path, source).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        warn!(
+            "could not save the best lap to {}: {}",
+            race.save_path.display(),
+            err
+        );
+    }
+}
+
+fn keyframe(time: f32, transform: &Transform, velocity: &Velocity) -> Keyframe {
+    Keyframe {
+        time,
+        position: transform.translation.xy(),
+        rotation: transform.rotation.to_euler(EulerRot::XYZ).2,
+        velocity: velocity.linvel,
+        angvel: velocity.angvel,
+    }
+}
+
+pub fn track_laps(
+    mut race: ResMut<Race>,
+    mut ships: Query<(
+        &Ship,
+        &Transform,
+        &Velocity,
+        &Controls,
+        &Reactor,
+        &mut RaceProgress,
+    )>,
+    players: Res<Players>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (ship, transform, velocity, controls, reactor, mut progress) in &mut ships {
+        if progress.finished {
+            continue;
+        }
+        let position = transform.translation.xy();
+        let gate = &race.checkpoints[progress.next];
+        let passed = level.wrap(gate.position - position).length() < gate.radius;
+
+        let Some(lap_started) = progress.lap_started else {
+            // the clock starts at the first pass through the start gate
+            if passed {
+                start_lap(&mut progress, now, transform, velocity, &race);
+            }
+            continue;
+        };
+
+        let lap_time = now - lap_started;
+        let input = RecordedInput {
+            time: lap_time,
+            thrust: controls.thrust,
+            turn: controls.turn,
+            power: reactor.pips,
+        };
+        let recording = &mut progress.recording;
+        let unchanged = recording.inputs.last().is_some_and(|last| {
+            last.thrust == input.thrust && last.turn == input.turn && last.power == input.power
+        });
+        if !unchanged {
+            recording.inputs.push(input);
+        }
+        let recent = recording
+            .keyframes
+            .last()
+            .is_some_and(|last| lap_time - last.time < KEYFRAME_INTERVAL);
+        if !recent {
+            recording
+                .keyframes
+                .push(keyframe(lap_time, transform, velocity));
+        }
+
+        if !passed {
+            continue;
+        }
+        if progress.next != 0 {
+            let split = progress.recording.splits.len();
+            progress.split_delta = race
+                .best
+                .as_ref()
+                .and_then(|best| best.splits.get(split))
+                .map(|best| lap_time - best);
+            progress.recording.splits.push(lap_time);
+            progress.next = (progress.next + 1) % race.checkpoints.len();
+            continue;
+        }
+
+        progress.laps += 1;
+        progress.last_lap = Some(lap_time);
+        let mut lap = std::mem::take(&mut progress.recording);
+        lap.time = lap_time;
+        lap.class = players
+            .get(ship.player)
+            .map_or(ShipClass::default(), |state| state.class)
+            .name()
+            .to_string();
+        if !race.best.as_ref().is_some_and(|best| best.time <= lap_time) {
+            info!(
+                "player {} set a new best lap: {:.2}",
+                ship.player + 1,
+                lap_time
+            );
+            race.best = Some(lap);
+            save_best_lap(&race);
+        }
+        if progress.laps >= level.rules.laps {
+            progress.finished = true;
+        } else {
+            start_lap(&mut progress, now, transform, velocity, &race);
+        }
+    }
+}
+
+fn start_lap(
+    progress: &mut RaceProgress,
+    now: f32,
+    transform: &Transform,
+    velocity: &Velocity,
+    race: &Race,
+) {
+    progress.lap_started = Some(now);
+    progress.next = 1 % race.checkpoints.len();
+    progress.split_delta = None;
+    progress.recording = LapRecord::default();
+    progress
+        .recording
+        .keyframes
+        .push(keyframe(0., transform, velocity));
+}
+
+// the ghost sets off whenever someone starts a lap and it is not already flying
+#[allow(clippy::too_many_arguments)]
+pub fn replay_ghost(
+    mut race: ResMut<Race>,
+    starters: Query<&RaceProgress>,
+    mut ghosts: Query<(&mut Controls, &mut Reactor, &mut Transform, &mut Velocity), With<Ghost>>,
+    mut commands: Commands,
+    mesh_handles: Res<MeshHandles>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    let Race { best, ghost, .. } = &mut *race;
+    let Some(best) = best.as_ref() else {
+        return;
+    };
+
+    let Some(run) = ghost.as_mut() else {
+        let lap_started = starters
+            .iter()
+            .any(|progress| progress.lap_started == Some(now));
+        let Some(start) = best.keyframes.first() else {
+            return;
+        };
+        if !lap_started {
+            return;
+        }
+        let spawn = SpawnPoint {
+            player: GHOST_PLAYER,
+            position: start.position,
+            rotation: start.rotation,
+            team: None,
+        };
+        let class = ShipClass::ALL
+            .into_iter()
+            .find(|class| class.name() == best.class)
+            .unwrap_or_default();
+        let entity = spawn_ship(
+            &mut commands,
+            &mesh_handles,
+            &mut materials,
+            &spawn,
+            class,
+            &level.rules,
+        );
+        // only the flying is replayed, it carries no cargo or trajectory of its own
+        commands
+            .entity(entity)
+            .remove::<(Cargo, Trajectory)>()
+            .insert((
+                Ghost,
+                Sensor,
+                CollisionCategory::Effect,
+                Velocity {
+                    linvel: start.velocity,
+                    angvel: start.angvel,
+                },
+                materials.add(ColorMaterial::from(Color::rgba(1., 1., 1., 0.3))),
+            ));
+        *ghost = Some(GhostRun {
+            entity,
+            started: now,
+            input: 0,
+            keyframe: 0,
+        });
+        return;
+    };
+
+    let t = now - run.started;
+    if t > best.time {
+        commands.entity(run.entity).despawn_recursive();
+        *ghost = None;
+        return;
+    }
+    let Ok((mut controls, mut reactor, mut transform, mut velocity)) = ghosts.get_mut(run.entity)
+    else {
+        return;
+    };
+    while run.input < best.inputs.len() && best.inputs[run.input].time <= t {
+        let input = best.inputs[run.input];
+        controls.thrust = input.thrust;
+        controls.turn = input.turn;
+        reactor.pips = input.power;
+        run.input += 1;
+    }
+    while run.keyframe < best.keyframes.len() && best.keyframes[run.keyframe].time <= t {
+        let keyframe = best.keyframes[run.keyframe];
+        transform.translation = keyframe.position.extend(transform.translation.z);
+        transform.rotation = Quat::from_rotation_z(keyframe.rotation);
+        velocity.linvel = keyframe.velocity;
+        velocity.angvel = keyframe.angvel;
+        run.keyframe += 1;
+    }
+}
+
+pub fn draw_checkpoints(race: Res<Race>, ships: Query<(&Ship, &RaceProgress)>, mut gizmos: Gizmos) {
+    for (index, gate) in race.checkpoints.iter().enumerate() {
+        let color = if index == 0 {
+            Color::WHITE
+        } else {
+            Color::GRAY
+        };
+        gizmos.circle_2d(gate.position, gate.radius, color);
+    }
+    for (ship, progress) in &ships {
+        if progress.finished {
+            continue;
+        }
+        let gate = &race.checkpoints[progress.next];
+        gizmos.circle_2d(
+            gate.position,
+            gate.radius - 4. - 3. * ship.player as f32,
+            player_color(ship.player),
+        );
+    }
+}
+
+pub fn update_race_text(
+    race: Res<Race>,
+    ships: Query<(&Ship, &RaceProgress)>,
+    mut texts: Query<(&RaceText, &mut Text)>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    let best = race
+        .best
+        .as_ref()
+        .map_or("-".to_string(), |best| format!("{:.2}", best.time));
+    for (race_text, mut text) in &mut texts {
+        let Some((_, progress)) = ships
+            .iter()
+            .find(|(ship, _)| ship.player == race_text.player)
+        else {
+            continue;
+        };
+        let current = match progress.lap_started {
+            _ if progress.finished => "finished".to_string(),
+            Some(started) => format!("{:.2}", time.elapsed_seconds() - started),
+            None => "-".to_string(),
+        };
+        let last = progress
+            .last_lap
+            .map_or("-".to_string(), |last| format!("{:.2}", last));
+        let split = progress
+            .split_delta
+            .map_or(String::new(), |delta| format!("  split {:+.2}", delta));
+        text.sections[0].value = format!(
+            "P{}  lap {}/{}  {}  last {}  best {}{}",
+            race_text.player + 1,
+            (progress.laps + 1).min(level.rules.laps),
+            level.rules.laps,
+            current,
+            last,
+            best,
+            split
+        );
+    }
+}
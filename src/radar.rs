@@ -0,0 +1,147 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::Velocity;
+
+use crate::camera::*;
+use crate::components::*;
+use crate::level::*;
+use crate::orbit::*;
+use crate::teams::*;
+
+const RADAR_SIZE: f32 = 160.;
+const RADAR_MARGIN: f32 = 12.;
+const RING_SEGMENTS: usize = 48;
+
+#[derive(Resource)]
+pub struct RadarSettings {
+    pub visible: bool,
+    // range rings are drawn around this player's ship
+    pub local_player: u8,
+}
+
+impl Default for RadarSettings {
+    fn default() -> Self {
+        RadarSettings {
+            visible: true,
+            local_player: 0,
+        }
+    }
+}
+
+pub fn toggle_radar(keyboard_input: Res<ButtonInput<KeyCode>>, mut radar: ResMut<RadarSettings>) {
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        radar.visible = !radar.visible;
+    }
+}
+
+// maps arena coordinates into the radar square in the bottom right corner of the screen
+struct RadarView {
+    center: Vec2,
+    scale: f32,
+}
+
+impl RadarView {
+    fn map(&self, position: Vec2) -> Vec2 {
+        self.center + position * self.scale
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_radar(
+    radar: Res<RadarSettings>,
+    cameras: Query<(&Camera, &Transform, &OrthographicProjection), With<CameraController>>,
+    ships: Query<(&Ship, &Team, &Transform, &Gun), Without<Ghost>>,
+    asteroids: Query<&Transform, With<Asteroid>>,
+    moons: Query<&Transform, With<Moon>>,
+    shots: Query<(&Transform, &Velocity, &Visibility), With<Shot>>,
+    level: Res<Level>,
+    mut gizmos: Gizmos,
+) {
+    if !radar.visible {
+        return;
+    }
+    let Ok((camera, camera_transform, projection)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+
+    // one screen pixel in world units
+    let pixel = projection.scale;
+    let half = RADAR_SIZE / 2. * pixel;
+    let corner = Vec2::new(viewport.x, -viewport.y) / 2. * pixel;
+    let view = RadarView {
+        center: camera_transform.translation.xy() + corner
+            - Vec2::new(half, -half)
+            - Vec2::new(RADAR_MARGIN, -RADAR_MARGIN) * pixel,
+        scale: half / level.space_size,
+    };
+
+    gizmos.rect_2d(view.center, 0., Vec2::splat(2. * half), Color::DARK_GRAY);
+
+    for moon in &moons {
+        let radius = (moon.scale.x * view.scale).max(3. * pixel);
+        gizmos.circle_2d(view.map(moon.translation.xy()), radius, Color::GRAY);
+    }
+    for asteroid in &asteroids {
+        let radius = (asteroid.scale.x * 6. * view.scale).max(pixel);
+        gizmos.circle_2d(
+            view.map(asteroid.translation.xy()),
+            radius,
+            Color::DARK_GRAY,
+        );
+    }
+    for (transform, velocity, visibility) in &shots {
+        // pooled shots are hidden until they are fired again
+        if visibility == Visibility::Hidden {
+            continue;
+        }
+        let position = view.map(transform.translation.xy());
+        let tail = velocity.linvel.normalize_or_zero() * 2. * pixel;
+        gizmos.line_2d(position - tail, position, Color::RED);
+    }
+    for (ship, team, transform, gun) in &ships {
+        let position = view.map(transform.translation.xy());
+        let forward = (transform.rotation * Vec3::Y).xy() * 4. * pixel;
+        let side = forward.perp() * 0.6;
+        gizmos.linestrip_2d(
+            [
+                position + forward,
+                position - forward + side,
+                position - forward - side,
+                position + forward,
+            ],
+            team_color(team.id),
+        );
+
+        if ship.player == radar.local_player {
+            let range = gun.range();
+            for radius in [range / 2., range] {
+                draw_ring(
+                    &mut gizmos,
+                    &view,
+                    &level,
+                    transform.translation.xy(),
+                    radius,
+                );
+            }
+        }
+    }
+}
+
+// rings wrap around the torus like everything else
+fn draw_ring(gizmos: &mut Gizmos, view: &RadarView, level: &Level, center: Vec2, radius: f32) {
+    let points: Vec<Vec2> = (0..=RING_SEGMENTS)
+        .map(|i| {
+            level.wrap(center + Vec2::from_angle(i as f32 * TAU / RING_SEGMENTS as f32) * radius)
+        })
+        .collect();
+    for segment in wrapped_segments(&points, level) {
+        gizmos.linestrip_2d(
+            segment.iter().map(|p| view.map(*p)),
+            Color::rgba(1., 1., 1., 0.2),
+        );
+    }
+}
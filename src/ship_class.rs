@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+use crate::players::*;
+use crate::{gamepad_for, get_key_config_for, spawn_ship, GamepadSlots, MeshHandles};
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum ShipClass {
+    // small and quick, but a weak shield and a single gun
+    Interceptor,
+    #[default]
+    Fighter,
+    // slow to turn and accelerate, heavily shielded with twin guns
+    Heavy,
+}
+
+pub struct ClassStats {
+    pub radius: f32,
+    pub density: f32,
+    pub thrust: f32,
+    pub flight_model: FlightModel,
+    pub turn_rate: f32,
+    pub angular_acceleration: f32,
+    // only used by the assisted flight model
+    pub rotation_damping: f32,
+    pub linear_drag: f32,
+    pub max_speed: Option<f32>,
+    pub shield_capacity: f32,
+    pub shield_regen: f32,
+    pub hull: f32,
+    pub gun_cooldown: f32,
+    pub shot_speed: f32,
+    pub shot_lifetime: f32,
+    pub barrels: u32,
+}
+
+impl ShipClass {
+    pub const ALL: [ShipClass; 3] = [ShipClass::Interceptor, ShipClass::Fighter, ShipClass::Heavy];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShipClass::Interceptor => "interceptor",
+            ShipClass::Fighter => "fighter",
+            ShipClass::Heavy => "heavy",
+        }
+    }
+
+    pub fn stats(self) -> ClassStats {
+        match self {
+            ShipClass::Interceptor => ClassStats {
+                radius: 12.,
+                density: 0.8,
+                thrust: 70000.,
+                flight_model: FlightModel::Arcade,
+                turn_rate: 7.,
+                angular_acceleration: 20.,
+                rotation_damping: 3.5,
+                linear_drag: 0.25,
+                max_speed: Some(420.),
+                shield_capacity: 0.7,
+                shield_regen: 1.2,
+                hull: 0.5,
+                gun_cooldown: 0.15,
+                shot_speed: 450.,
+                shot_lifetime: 0.8,
+                barrels: 1,
+            },
+            ShipClass::Fighter => ClassStats {
+                radius: 16.,
+                density: 1.,
+                thrust: 100000.,
+                flight_model: FlightModel::Arcade,
+                turn_rate: 5.,
+                angular_acceleration: 12.,
+                rotation_damping: 3.,
+                linear_drag: 0.3,
+                max_speed: Some(350.),
+                shield_capacity: 1.,
+                shield_regen: 1.,
+                hull: 0.7,
+                gun_cooldown: 0.2,
+                shot_speed: 400.,
+                shot_lifetime: 1.,
+                barrels: 1,
+            },
+            ShipClass::Heavy => ClassStats {
+                radius: 22.,
+                density: 1.5,
+                thrust: 150000.,
+                flight_model: FlightModel::Arcade,
+                turn_rate: 3.5,
+                angular_acceleration: 7.,
+                rotation_damping: 2.5,
+                linear_drag: 0.35,
+                max_speed: Some(280.),
+                shield_capacity: 1.6,
+                shield_regen: 0.8,
+                hull: 1.1,
+                gun_cooldown: 0.35,
+                shot_speed: 350.,
+                shot_lifetime: 1.2,
+                barrels: 2,
+            },
+        }
+    }
+
+    pub fn mesh(self, mesh_handles: &MeshHandles) -> Handle<Mesh> {
+        match self {
+            ShipClass::Interceptor => mesh_handles.ship.clone(),
+            ShipClass::Fighter => mesh_handles.fighter.clone(),
+            ShipClass::Heavy => mesh_handles.heavy.clone(),
+        }
+    }
+
+    // a level can force one flight model on every class
+    pub fn flight(self, level_model: Option<FlightModel>) -> Flight {
+        let stats = self.stats();
+        Flight {
+            model: level_model.unwrap_or(stats.flight_model),
+            turn_rate: stats.turn_rate,
+            angular_acceleration: stats.angular_acceleration,
+            rotation_damping: stats.rotation_damping,
+            linear_drag: stats.linear_drag,
+            max_speed: stats.max_speed,
+        }
+    }
+
+    fn cycle(self, step: i32) -> ShipClass {
+        let len = ShipClass::ALL.len() as i32;
+        let index = ShipClass::ALL.iter().position(|c| *c == self).unwrap() as i32;
+        ShipClass::ALL[(index + step).rem_euclid(len) as usize]
+    }
+}
+
+pub struct ClassChoice {
+    pub player: u8,
+    pub class: ShipClass,
+    pub ready: bool,
+}
+
+// exists only while the players are picking their ships, the match starts once everyone is ready
+#[derive(Resource)]
+pub struct ClassSelection {
+    pub choices: Vec<ClassChoice>,
+}
+
+pub fn setup_class_selection(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level: Res<Level>,
+) {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+
+    commands.insert_resource(ClassSelection {
+        choices: level
+            .spawns
+            .iter()
+            .map(|spawn| ClassChoice {
+                player: spawn.player,
+                class: ShipClass::default(),
+                ready: false,
+            })
+            .collect(),
+    });
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                },
+                ..default()
+            },
+            ClassSelectionRoot,
+        ))
+        .with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "turn to pick a ship, shoot when ready",
+                text_style.clone(),
+            ));
+            for spawn in &level.spawns {
+                root.spawn((
+                    TextBundle::from_section("", text_style.clone()),
+                    ClassChoiceText {
+                        player: spawn.player,
+                    },
+                ));
+            }
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn select_classes(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<GamepadSlots>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut selection: ResMut<ClassSelection>,
+    mut players: ResMut<Players>,
+    roots: Query<Entity, With<ClassSelectionRoot>>,
+    mut commands: Commands,
+    mesh_handles: Res<MeshHandles>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    level: Res<Level>,
+) {
+    for choice in &mut selection.choices {
+        let keys = get_key_config_for(choice.player);
+        let gamepad = gamepad_for(&gamepads, choice.player);
+        let pressed = |key: Option<KeyCode>, button_type| {
+            key.is_some_and(|key| keyboard_input.just_pressed(key))
+                || gamepad.is_some_and(|gamepad| {
+                    gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type))
+                })
+        };
+        if pressed(keys.as_ref().map(|k| k.shoot), GamepadButtonType::South) {
+            choice.ready = !choice.ready;
+        }
+        if choice.ready {
+            continue;
+        }
+        if pressed(keys.as_ref().map(|k| k.left), GamepadButtonType::DPadLeft) {
+            choice.class = choice.class.cycle(-1);
+        } else if pressed(keys.as_ref().map(|k| k.right), GamepadButtonType::DPadRight) {
+            choice.class = choice.class.cycle(1);
+        }
+    }
+
+    if !selection.choices.iter().all(|choice| choice.ready) {
+        return;
+    }
+    for choice in &selection.choices {
+        if let Some(state) = players.get_mut(choice.player) {
+            state.class = choice.class;
+        }
+        if let Some(spawn) = level.spawns.iter().find(|s| s.player == choice.player) {
+            spawn_ship(
+                &mut commands,
+                &mesh_handles,
+                &mut materials,
+                spawn,
+                choice.class,
+                &level.rules,
+            );
+        }
+    }
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+    commands.remove_resource::<ClassSelection>();
+}
+
+pub fn update_class_choice_text(
+    selection: Res<ClassSelection>,
+    mut texts: Query<(&ClassChoiceText, &mut Text)>,
+) {
+    if !selection.is_changed() {
+        return;
+    }
+    for (choice_text, mut text) in &mut texts {
+        let Some(choice) = selection
+            .choices
+            .iter()
+            .find(|c| c.player == choice_text.player)
+        else {
+            continue;
+        };
+        text.sections[0].value = format!(
+            "P{}  < {} >{}",
+            choice.player + 1,
+            choice.class.name(),
+            if choice.ready { "  ready" } else { "" }
+        );
+        text.sections[0].style.color = player_color(choice.player);
+    }
+}
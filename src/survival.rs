@@ -0,0 +1,229 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+use crate::mining::random_ore;
+use crate::players::*;
+use crate::ship_class::*;
+use crate::teams::NPC_TEAM;
+use crate::{spawn_asteroid, spawn_ship, MeshHandles};
+
+// enemy ships and their shots belong to this player, who never scores
+pub const NPC_PLAYER: u8 = u8::MAX;
+
+const WAVE_BREAK: f32 = 4.;
+const MAX_WAVE_SIZE: u32 = 20;
+const SNIPER_DISTANCE: f32 = 300.;
+const CHASER_DISTANCE: f32 = 120.;
+const AIM_TOLERANCE: f32 = 0.15;
+const THRUST_TOLERANCE: f32 = 0.5;
+
+#[derive(Resource)]
+pub struct Survival {
+    pub wave: u32,
+    // enemies of the current wave that have not been spawned yet
+    pub queue: Vec<NpcKind>,
+    // infinite while the last enemies of a wave are still flying
+    pub next_spawn_at: f32,
+    pub over: bool,
+}
+
+impl Survival {
+    // every wave is bigger, spawns faster and brings in the tougher behaviours
+    fn wave_plan(wave: u32) -> Vec<NpcKind> {
+        (0..(wave + 2).min(MAX_WAVE_SIZE))
+            .map(|i| match i {
+                i if wave >= 3 && i % 4 == 3 => NpcKind::Rammer,
+                i if wave >= 2 && i % 3 == 2 => NpcKind::Sniper,
+                _ => NpcKind::Chaser,
+            })
+            .collect()
+    }
+
+    fn spawn_interval(&self) -> f32 {
+        (2. - 0.15 * self.wave as f32).max(0.4)
+    }
+}
+
+pub fn setup_survival(mut commands: Commands, asset_server: Res<AssetServer>, level: Res<Level>) {
+    if level.rules.mode != GameMode::Survival {
+        return;
+    }
+    commands.insert_resource(Survival {
+        wave: 0,
+        queue: Vec::new(),
+        next_spawn_at: f32::INFINITY,
+        over: false,
+    });
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_text_justify(JustifyText::Center),
+        WaveText,
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_waves(
+    mut survival: ResMut<Survival>,
+    npcs: Query<(), With<Npc>>,
+    players: Res<Players>,
+    player_ships: Query<(), (With<Ship>, Without<Npc>)>,
+    mut commands: Commands,
+    mesh_handles: Res<MeshHandles>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    level: Res<Level>,
+    time: Res<Time>,
+) {
+    if survival.over {
+        return;
+    }
+    if players.team_lives == Some(0) && player_ships.is_empty() {
+        survival.over = true;
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    if survival.queue.is_empty() {
+        if !npcs.is_empty() {
+            return;
+        }
+        if survival.next_spawn_at == f32::INFINITY {
+            survival.next_spawn_at = now + WAVE_BREAK;
+        }
+        if now < survival.next_spawn_at {
+            return;
+        }
+        survival.wave += 1;
+        survival.queue = Survival::wave_plan(survival.wave);
+        for _ in 0..survival.wave + 1 {
+            spawn_asteroid(
+                &mut commands,
+                random_position(&level).extend(0.),
+                &mesh_handles,
+                3.,
+                Velocity::linear(Vec2::from_angle(rand::random::<f32>() * TAU) * 20.),
+                random_ore(),
+            );
+        }
+    }
+
+    if now < survival.next_spawn_at {
+        return;
+    }
+    if let Some(kind) = survival.queue.pop() {
+        let spawn = SpawnPoint {
+            player: NPC_PLAYER,
+            position: random_edge_position(&level),
+            rotation: rand::random::<f32>() * TAU,
+            team: Some(NPC_TEAM),
+        };
+        let ship = spawn_ship(
+            &mut commands,
+            &mesh_handles,
+            &mut materials,
+            &spawn,
+            ShipClass::Fighter,
+            &level.rules,
+        );
+        commands.entity(ship).insert(Npc { kind });
+        survival.next_spawn_at = if survival.queue.is_empty() {
+            f32::INFINITY
+        } else {
+            now + survival.spawn_interval()
+        };
+    }
+}
+
+fn random_position(level: &Level) -> Vec2 {
+    (Vec2::new(rand::random(), rand::random()) * 2. - 1.) * level.space_size
+}
+
+fn random_edge_position(level: &Level) -> Vec2 {
+    let along = (rand::random::<f32>() * 2. - 1.) * level.space_size;
+    let edge = level.space_size * 0.95;
+    match rand::random::<u8>() % 4 {
+        0 => Vec2::new(along, edge),
+        1 => Vec2::new(along, -edge),
+        2 => Vec2::new(edge, along),
+        _ => Vec2::new(-edge, along),
+    }
+}
+
+pub fn steer_npcs(
+    mut npcs: Query<(&Npc, &Transform, &Velocity, &Gun, &mut Controls)>,
+    targets: Query<&Transform, (With<Ship>, Without<Npc>)>,
+    level: Res<Level>,
+) {
+    for (npc, transform, velocity, gun, mut controls) in &mut npcs {
+        let position = transform.translation.xy();
+        // the shortest way to a target may lead across the arena edge
+        let Some(offset) = targets
+            .iter()
+            .map(|target| level.wrap(target.translation.xy() - position))
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        else {
+            *controls = Controls::default();
+            continue;
+        };
+        let distance = offset.length();
+        let forward = (transform.rotation * Vec3::Y).xy();
+
+        let flee = npc.kind == NpcKind::Sniper && distance < SNIPER_DISTANCE * 0.6;
+        let heading = if flee { -offset } else { offset };
+        let angle = forward.angle_between(heading);
+        controls.turn = (2. * angle - 0.3 * velocity.angvel).clamp(-1., 1.);
+
+        let aligned = angle.abs() < THRUST_TOLERANCE;
+        controls.thrust = aligned
+            && match npc.kind {
+                NpcKind::Chaser => distance > CHASER_DISTANCE,
+                NpcKind::Sniper => flee || distance > SNIPER_DISTANCE,
+                NpcKind::Rammer => true,
+            };
+        controls.shoot = npc.kind != NpcKind::Rammer
+            && forward.angle_between(offset).abs() < AIM_TOLERANCE
+            && distance < gun.range();
+    }
+}
+
+pub fn update_wave_text(
+    survival: Res<Survival>,
+    players: Res<Players>,
+    mut texts: Query<&mut Text, With<WaveText>>,
+) {
+    if !survival.is_changed() && !players.is_changed() {
+        return;
+    }
+    let value = if survival.over {
+        format!(
+            "game over, survived {} waves",
+            survival.wave.saturating_sub(1)
+        )
+    } else {
+        format!(
+            "wave {}  team lives {}",
+            survival.wave,
+            players.team_lives.unwrap_or(0)
+        )
+    };
+    for mut text in &mut texts {
+        text.sections[0].value = value.clone();
+    }
+}
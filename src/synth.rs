@@ -0,0 +1,160 @@
+// Sound effects are generated at startup instead of loaded from files.
+// Everything here works on plain sample buffers, no audio device involved.
+
+use std::f32::consts::TAU;
+
+pub const SAMPLE_RATE: u32 = 22050;
+
+// small deterministic noise source, the same seed always gives the same sound
+pub struct Noise(u32);
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        Noise(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2. - 1.
+    }
+}
+
+fn sample_count(duration: f32) -> usize {
+    (duration * SAMPLE_RATE as f32) as usize
+}
+
+// white noise fading out exponentially, `decay` is the number of e-foldings over the duration
+pub fn noise_burst(duration: f32, decay: f32, seed: u32) -> Vec<f32> {
+    let mut noise = Noise::new(seed);
+    let len = sample_count(duration);
+    (0..len)
+        .map(|i| noise.next() * (-decay * i as f32 / len as f32).exp())
+        .collect()
+}
+
+// sine wave gliding from `from` to `to` Hz with a linear fade out
+pub fn sweep(from: f32, to: f32, duration: f32) -> Vec<f32> {
+    let len = sample_count(duration);
+    let mut phase = 0.;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / len as f32;
+            phase += (from + (to - from) * t) / SAMPLE_RATE as f32;
+            (phase * TAU).sin() * (1. - t)
+        })
+        .collect()
+}
+
+// one second of low rumble for the thruster, meant to be looped
+pub fn engine_loop(seed: u32) -> Vec<f32> {
+    let mut noise = Noise::new(seed);
+    let len = SAMPLE_RATE as usize;
+    let mut low = 0.;
+    (0..len)
+        .map(|i| {
+            low += (noise.next() - low) * 0.05;
+            let hum = (i as f32 * 55. * TAU / SAMPLE_RATE as f32).sin();
+            // a whole number of hum cycles per second keeps the loop point smooth
+            low * 2. + hum * 0.3
+        })
+        .collect()
+}
+
+pub fn mix(a: &[f32], b: &[f32]) -> Vec<f32> {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0.) + b.get(i).unwrap_or(&0.))
+        .collect()
+}
+
+pub fn gain(samples: &[f32], gain: f32) -> Vec<f32> {
+    samples.iter().map(|s| s * gain).collect()
+}
+
+// 16 bit mono PCM in a wav container, which the audio backend can decode
+pub fn to_wav(samples: &[f32]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1., 1.) * i16::MAX as f32) as i16;
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn i16_at(bytes: &[u8], at: usize) -> i16 {
+        i16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn buffers_last_their_duration() {
+        assert_eq!(sweep(880., 220., 0.5).len(), SAMPLE_RATE as usize / 2);
+        assert_eq!(
+            noise_burst(0.2, 4., 7).len(),
+            (0.2 * SAMPLE_RATE as f32) as usize
+        );
+        assert_eq!(engine_loop(3).len(), SAMPLE_RATE as usize);
+        assert!(sweep(440., 440., 0.).is_empty());
+    }
+
+    #[test]
+    fn samples_stay_in_range() {
+        let mut noise = Noise::new(0);
+        assert!((0..10_000)
+            .map(|_| noise.next())
+            .all(|s| (-1. ..=1.).contains(&s)));
+        assert!(noise_burst(0.3, 2., 11).iter().all(|s| s.abs() <= 1.));
+        assert!(sweep(100., 2000., 0.3).iter().all(|s| s.abs() <= 1.));
+    }
+
+    #[test]
+    fn mix_is_as_long_as_the_longer_buffer() {
+        let mixed = mix(&[0.5, 0.5, 0.5], &[0.25]);
+        assert_eq!(mixed, vec![0.75, 0.5, 0.5]);
+        assert_eq!(mix(&[0.1], &[0.; 5]).len(), 5);
+    }
+
+    #[test]
+    fn wav_header_matches_the_data() {
+        let samples = sweep(440., 440., 0.1);
+        let wav = to_wav(&samples);
+        let data_len = samples.len() as u32 * 2;
+        assert_eq!(wav.len(), 44 + data_len as usize);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4), wav.len() as u32 - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&wav, 24), SAMPLE_RATE);
+        assert_eq!(u32_at(&wav, 28), SAMPLE_RATE * 2);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40), data_len);
+    }
+
+    #[test]
+    fn wav_clamps_loud_samples() {
+        let wav = to_wav(&[2., -3., 0.]);
+        assert_eq!(i16_at(&wav, 44), i16::MAX);
+        assert_eq!(i16_at(&wav, 46), -i16::MAX);
+        assert_eq!(i16_at(&wav, 48), 0);
+    }
+}
@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+use crate::level::*;
+
+// enemy fighters in survival all fly for this team
+pub const NPC_TEAM: u8 = u8::MAX;
+
+const TEAM_COLORS: [Color; 4] = [
+    Color::rgb(0.3, 0.6, 1.),
+    Color::rgb(1., 0.6, 0.2),
+    Color::rgb(0.4, 1., 0.4),
+    Color::rgb(1., 0.4, 1.),
+];
+const REDUCED_FRIENDLY_FIRE: f32 = 0.3;
+
+pub fn team_color(team: u8) -> Color {
+    if team == NPC_TEAM {
+        return Color::RED;
+    }
+    TEAM_COLORS[team as usize % TEAM_COLORS.len()]
+}
+
+// how much of the damage gets through when both sides fly for the same team
+pub fn friendly_fire_factor(friendly_fire: FriendlyFire) -> f32 {
+    match friendly_fire {
+        FriendlyFire::Off => 0.,
+        FriendlyFire::Reduced => REDUCED_FRIENDLY_FIRE,
+        FriendlyFire::Full => 1.,
+    }
+}
@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::level::*;
+use crate::orbit::*;
+use crate::{gamepad_for, get_key_config_for, GamepadSlots};
+
+const TRAJECTORY_STEPS_PER_SECOND: f32 = 40.;
+
+pub fn toggle_trajectories(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<GamepadSlots>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut ships: Query<(&Ship, &mut Trajectory)>,
+) {
+    for (ship, mut trajectory) in &mut ships {
+        let key = get_key_config_for(ship.player)
+            .is_some_and(|keys| keyboard_input.just_pressed(keys.trajectory));
+        let button = gamepad_for(&gamepads, ship.player).is_some_and(|gamepad| {
+            gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::North))
+        });
+        if key || button {
+            trajectory.visible = !trajectory.visible;
+        }
+    }
+}
+
+// the ship drifts with everything else, so all masses are predicted together
+pub fn draw_trajectories(
+    ships: Query<(Entity, &Trajectory)>,
+    bodies: Query<(Entity, OrbitBodyData)>,
+    transforms: Query<&Transform>,
+    level: Res<Level>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+) {
+    let visible: Vec<(Entity, f32)> = ships
+        .iter()
+        .filter(|(_, trajectory)| trajectory.visible)
+        .map(|(entity, trajectory)| (entity, trajectory.seconds))
+        .collect();
+    let Some(seconds) = visible.iter().map(|(_, seconds)| *seconds).reduce(f32::max) else {
+        return;
+    };
+
+    let (entities, bodies): (Vec<Entity>, Vec<OrbitBody>) = bodies
+        .iter()
+        .map(|(entity, body)| (entity, OrbitBody::new(body, &transforms)))
+        .unzip();
+    let steps = (seconds * TRAJECTORY_STEPS_PER_SECOND).ceil() as usize;
+    let paths = predict_orbits(&bodies, &level, time.elapsed_seconds(), seconds, steps);
+
+    for (ship, seconds) in visible {
+        if let Some(index) = entities.iter().position(|entity| *entity == ship) {
+            let len = (seconds * TRAJECTORY_STEPS_PER_SECOND).ceil() as usize + 1;
+            let path = &paths[index][..len.min(paths[index].len())];
+            draw_wrapped_path(&mut gizmos, path, &level, Color::GREEN);
+        }
+    }
+}